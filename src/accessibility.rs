@@ -0,0 +1,446 @@
+use bevy::{prelude::*, sprite::Anchor};
+use serde::{Deserialize, Serialize};
+
+use crate::animation::{AnimationConfig, Easing, Tween, Tweens};
+use crate::menu::AppState;
+use crate::merge::MergeRule;
+use crate::theme::Themes;
+use crate::tile::value::Value;
+use crate::{SquareId, Transparency, TILE_SIZE};
+
+/// Color vision deficiencies the palettes are checked against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorVision {
+    Normal,
+    Deuteranopia,
+    Protanopia,
+}
+
+impl ColorVision {
+    pub const ALL: [ColorVision; 3] = [
+        ColorVision::Normal,
+        ColorVision::Deuteranopia,
+        ColorVision::Protanopia,
+    ];
+
+    /// Machado et al. (2009) matrices at full severity, applied to linear RGB.
+    fn matrix(&self) -> [[f32; 3]; 3] {
+        match self {
+            ColorVision::Normal => [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            ColorVision::Deuteranopia => [
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ],
+            ColorVision::Protanopia => [
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ],
+        }
+    }
+
+    /// How `color` looks to someone with this kind of color vision.
+    pub fn simulate(&self, color: Color) -> Color {
+        let [r, g, b, a] = color.as_linear_rgba_f32();
+        let m = self.matrix();
+        let row = |i: usize| (m[i][0] * r + m[i][1] * g + m[i][2] * b).clamp(0.0, 1.0);
+        Color::rgba_linear(row(0), row(1), row(2), a)
+    }
+}
+
+/// Relative luminance as defined by WCAG.
+pub fn luminance(color: Color) -> f32 {
+    let [r, g, b, _] = color.as_linear_rgba_f32();
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+/// WCAG contrast ratio between two colors, from 1 to 21.
+pub fn contrast_ratio(a: Color, b: Color) -> f32 {
+    let (la, lb) = (luminance(a), luminance(b));
+    (la.max(lb) + 0.05) / (la.min(lb) + 0.05)
+}
+
+/// Black or white, whichever is easier to read on `background`.
+pub fn contrast_text(background: Color) -> Color {
+    if contrast_ratio(background, Color::BLACK) >= contrast_ratio(background, Color::WHITE) {
+        Color::BLACK
+    } else {
+        Color::WHITE
+    }
+}
+
+/// CIE L*a*b* coordinates of `color` under a D65 white point.
+pub fn to_lab(color: Color) -> Vec3 {
+    let [r, g, b, _] = color.as_linear_rgba_f32();
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+    let f = |t: f32| {
+        if t > 0.008856 {
+            t.cbrt()
+        } else {
+            7.787 * t + 16.0 / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    Vec3::new(116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz))
+}
+
+/// Perceived difference between two colors (CIE76 delta E).
+pub fn color_distance(a: Color, b: Color) -> f32 {
+    to_lab(a).distance(to_lab(b))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Motion {
+    Full,
+    /// Short animations without overshoot, pops or bounces.
+    Reduced,
+    /// Tiles jump to their new place.
+    Off,
+}
+
+impl Motion {
+    pub fn next(&self) -> Motion {
+        match self {
+            Motion::Full => Motion::Reduced,
+            Motion::Reduced => Motion::Off,
+            Motion::Off => Motion::Full,
+        }
+    }
+
+    /// The tweens `tweens` are played with under this setting. Reduced
+    /// motion plays them in half the time on a straight curve and drops the
+    /// pop of merges.
+    pub fn tweens(&self, tweens: &Tweens) -> Tweens {
+        let linear =
+            |tween: Tween, factor: f32| Tween::new(tween.duration * factor, Easing::Linear);
+        match self {
+            Motion::Full => *tweens,
+            Motion::Reduced => Tweens {
+                slide: linear(tweens.slide, 0.5),
+                merge: linear(tweens.merge, 0.0),
+                spawn: linear(tweens.spawn, 0.5),
+            },
+            Motion::Off => Tweens {
+                slide: linear(tweens.slide, 0.0),
+                merge: linear(tweens.merge, 0.0),
+                spawn: linear(tweens.spawn, 0.0),
+            },
+        }
+    }
+}
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct Accessibility {
+    /// Picks black or white tile text by the tile's luminance instead of
+    /// the theme's text colors.
+    pub contrast_text: bool,
+    /// Shows the exponent in the corner of every tile, so tiles can be told
+    /// apart without relying on color.
+    pub badges: bool,
+    pub motion: Motion,
+}
+
+impl Default for Accessibility {
+    fn default() -> Self {
+        Accessibility {
+            contrast_text: false,
+            badges: false,
+            motion: Motion::Full,
+        }
+    }
+}
+
+/// Small exponent label in the top left corner of a tile.
+#[derive(Component)]
+pub struct Badge;
+
+fn toggle_accessibility(keys: Res<ButtonInput<KeyCode>>, mut settings: ResMut<Accessibility>) {
+    if keys.just_pressed(KeyCode::KeyC) {
+        settings.contrast_text = !settings.contrast_text;
+    }
+    if keys.just_pressed(KeyCode::KeyB) {
+        settings.badges = !settings.badges;
+    }
+    if keys.just_pressed(KeyCode::KeyM) {
+        settings.motion = settings.motion.next();
+    }
+}
+
+fn apply_accessibility(settings: Res<Accessibility>, mut themes: ResMut<Themes>) {
+    if themes.contrast_text != settings.contrast_text {
+        themes.contrast_text = settings.contrast_text;
+    }
+}
+
+/// Animations keep the configured tweens, the motion setting only changes
+/// how they are played.
+fn apply_motion(settings: Res<Accessibility>, mut config: ResMut<AnimationConfig>) {
+    if config.motion != settings.motion {
+        config.motion = settings.motion;
+    }
+}
+
+/// The rung of `value` on the ladder of `rule`, counted like the exponents
+/// of classic tiles.
+fn badge_text(value: &Value, rule: MergeRule) -> String {
+    match value {
+        Value::Empty | Value::Wall => "".to_string(),
+        Value::Number(_) => rule.classic(value).to_exponent().to_string(),
+    }
+}
+
+fn spawn_badge(commands: &mut Commands, themes: &Themes, tile: Entity, value: &Value) {
+    let corner = TILE_SIZE / 2.0 - 6.0;
+    let badge = Text2dBundle {
+        text: Text::from_section(
+            badge_text(value, themes.rule),
+            TextStyle {
+                font: themes.font.clone(),
+                font_size: 18.0,
+                color: themes.tile_text_color(value),
+            },
+        ),
+        text_anchor: Anchor::TopLeft,
+        transform: Transform {
+            translation: Vec2::new(-corner, corner).extend(0.3),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let badge_id = commands
+        .spawn(badge)
+        .insert(Badge)
+        .insert(Transparency(0.0))
+        .id();
+    commands.entity(tile).add_child(badge_id);
+}
+
+/// Adds badges to new tiles and removes all of them once badges are
+/// switched off. Existing badges follow the tile's value and the theme.
+fn update_badges(
+    mut commands: Commands,
+    settings: Res<Accessibility>,
+    themes: Res<Themes>,
+    tiles: Query<(Entity, &Value, Option<&Children>), With<SquareId>>,
+    mut badges: Query<(Entity, &mut Text), With<Badge>>,
+) {
+    if !settings.badges {
+        for (badge, _) in badges.iter() {
+            commands.entity(badge).despawn_recursive();
+        }
+        return;
+    }
+    for (tile, value, children) in tiles.iter() {
+        let badge = children
+            .into_iter()
+            .flatten()
+            .find(|child| badges.contains(**child));
+        match badge {
+            Some(badge) => {
+                let (_, mut text) = badges.get_mut(*badge).unwrap();
+                let section = &mut text.sections[0];
+                let label = badge_text(value, themes.rule);
+                let color = themes.tile_text_color(value);
+                if section.value != label
+                    || section.style.color != color
+                    || section.style.font != themes.font
+                {
+                    section.value = label;
+                    section.style.color = color;
+                    section.style.font = themes.font.clone();
+                }
+            }
+            None => spawn_badge(&mut commands, &themes, tile, value),
+        }
+    }
+}
+
+pub struct AccessibilityPlugin;
+
+impl Plugin for AccessibilityPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Accessibility>()
+            .add_systems(
+                PreUpdate,
+                (
+                    // the editor takes typed letters as tiles
+                    toggle_accessibility.run_if(not(in_state(AppState::Editor))),
+                    (apply_accessibility, apply_motion).run_if(resource_changed::<Accessibility>),
+                )
+                    .chain(),
+            )
+            .add_systems(PostUpdate, update_badges);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::theme::Theme;
+
+    /// Smallest delta E that still reads as a clearly different color.
+    const MIN_DISTANCE: f32 = 10.0;
+
+    fn theme(name: &str) -> Theme {
+        Theme::built_in()
+            .into_iter()
+            .find(|theme| theme.name == name)
+            .unwrap()
+    }
+
+    fn closest_pair(theme: &Theme, vision: ColorVision) -> (f32, usize, usize) {
+        let colors: Vec<Color> = theme
+            .tiles
+            .iter()
+            .map(|tile| tile.background)
+            .chain([theme.empty_tile, theme.wall])
+            .map(|color| vision.simulate(color))
+            .collect();
+        let mut closest = (f32::MAX, 0, 0);
+        for i in 0..colors.len() {
+            for j in i + 1..colors.len() {
+                let distance = color_distance(colors[i], colors[j]);
+                if distance < closest.0 {
+                    closest = (distance, i, j);
+                }
+            }
+        }
+        closest
+    }
+
+    #[test]
+    fn test_colorblind_palette_stays_distinct() {
+        let colorblind = theme("colorblind");
+        for vision in ColorVision::ALL.iter() {
+            let (distance, i, j) = closest_pair(&colorblind, *vision);
+            assert!(
+                distance >= MIN_DISTANCE,
+                "{:?}: tiles {} and {} are {} apart",
+                vision,
+                i,
+                j,
+                distance
+            );
+        }
+    }
+
+    #[test]
+    fn test_classic_palette_collapses_for_deuteranopia() {
+        // the reason the colorblind theme exists
+        let (distance, _, _) = closest_pair(&theme("classic"), ColorVision::Deuteranopia);
+        assert!(distance < MIN_DISTANCE, "{}", distance);
+    }
+
+    #[test]
+    fn test_simulation_keeps_grays() {
+        let gray = Color::rgb(0.5, 0.5, 0.5);
+        for vision in ColorVision::ALL.iter() {
+            assert!(
+                color_distance(vision.simulate(gray), gray) < 1.0,
+                "{:?}",
+                vision
+            );
+        }
+    }
+
+    #[test]
+    fn test_simulation_merges_red_and_green() {
+        let normal = color_distance(Color::RED, Color::GREEN);
+        for vision in [ColorVision::Deuteranopia, ColorVision::Protanopia].iter() {
+            let simulated =
+                color_distance(vision.simulate(Color::RED), vision.simulate(Color::GREEN));
+            assert!(simulated < normal / 2.0, "{:?}: {}", vision, simulated);
+        }
+    }
+
+    #[test]
+    fn test_contrast_text() {
+        assert_eq!(contrast_text(Color::WHITE), Color::BLACK);
+        assert_eq!(contrast_text(Color::BLACK), Color::WHITE);
+        assert_eq!(contrast_text(Color::hex("#edc22e").unwrap()), Color::BLACK);
+        assert_eq!(contrast_text(Color::hex("#08306b").unwrap()), Color::WHITE);
+        assert!((contrast_ratio(Color::WHITE, Color::BLACK) - 21.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_contrast_text_is_readable_on_every_tile() {
+        for theme in Theme::built_in().iter() {
+            for tile in theme.tiles.iter() {
+                let text = contrast_text(tile.background);
+                assert!(
+                    contrast_ratio(text, tile.background) >= 4.5,
+                    "{} {:?}",
+                    theme.name,
+                    tile.background
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_motion_settings() {
+        let tile = crate::tile::Tile {
+            value: Value::Number(2),
+            position: crate::tile::position::Position { row: 0, col: 0 },
+        };
+        let spawning = crate::animation::Animation::Spawning {
+            entity: Entity::from_raw(0),
+            tile,
+        };
+        let duration = |motion| {
+            AnimationConfig {
+                motion,
+                ..Default::default()
+            }
+            .duration(&spawning)
+        };
+        let (full, reduced, off) = (
+            duration(Motion::Full),
+            duration(Motion::Reduced),
+            duration(Motion::Off),
+        );
+        assert!(
+            full > reduced && reduced > off,
+            "{} {} {}",
+            full,
+            reduced,
+            off
+        );
+        assert_eq!(off, 0.0);
+        assert_eq!(Motion::Off.next(), Motion::Full);
+    }
+
+    #[test]
+    fn test_motion_keeps_the_configured_tweens() {
+        let mut tweens = Tweens {
+            slide: Tween::new(0.3, Easing::Spring),
+            ..Default::default()
+        };
+        tweens.merge.easing = Easing::ElasticOut;
+        assert_eq!(Motion::Full.tweens(&tweens), tweens);
+        let reduced = Motion::Reduced.tweens(&tweens);
+        assert_eq!(reduced.slide, Tween::new(0.15, Easing::Linear));
+        assert_eq!(reduced.merge.duration, 0.0);
+
+        let mut app = App::new();
+        app.insert_resource(Accessibility::default())
+            .insert_resource(AnimationConfig {
+                tweens,
+                ..Default::default()
+            })
+            .add_systems(Update, apply_motion);
+        app.update();
+        let config = app.world.resource::<AnimationConfig>();
+        assert_eq!(config.tweens, tweens);
+        assert_eq!(config.played(), tweens);
+
+        app.world.resource_mut::<Accessibility>().motion = Motion::Reduced;
+        app.update();
+        let config = app.world.resource::<AnimationConfig>();
+        assert_eq!(config.tweens, tweens);
+        assert_eq!(config.played(), reduced);
+    }
+}
@@ -7,4 +7,26 @@ pub enum Action {
     SpawnRandomTile(Tile),
     SlideTile(Tile, Position),
     MergeTiles(Tile, Tile, Position, Value),
+    /// Three tiles in a row merging into one, see `MergeRule::Triples`.
+    MergeThreeTiles(Tile, Tile, Tile, Position, Value),
+}
+
+impl Action {
+    /// Points the action is worth, merges score the value they create.
+    pub fn score(&self) -> u32 {
+        match self.merged() {
+            Some((_, Value::Number(n))) => n,
+            _ => 0,
+        }
+    }
+
+    /// Where a merge puts the tile it creates and its value.
+    pub fn merged(&self) -> Option<(Position, Value)> {
+        match self {
+            Action::MergeTiles(_, _, to, value) | Action::MergeThreeTiles(_, _, _, to, value) => {
+                Some((*to, *value))
+            }
+            Action::SpawnRandomTile(_) | Action::SlideTile(..) => None,
+        }
+    }
 }
@@ -0,0 +1,559 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::accessibility::Motion;
+use crate::score;
+use crate::tile::{position::Position, value::Value, Tile};
+use crate::{to_screen, TextId, TextMarker, Transparency, TILE_GAP, TILE_SIZE};
+
+/// How much the merged tile grows while it pops. The pop follows the merge
+/// easing curve minus a linear ramp, so curves without overshoot barely pop.
+const MERGE_POP_STRENGTH: f32 = 0.4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Easing {
+    Linear,
+    CubicOut,
+    CubicInOut,
+    BackOut,
+    ElasticOut,
+    Spring,
+}
+
+impl Easing {
+    pub const ALL: [Easing; 6] = [
+        Easing::Linear,
+        Easing::CubicOut,
+        Easing::CubicInOut,
+        Easing::BackOut,
+        Easing::ElasticOut,
+        Easing::Spring,
+    ];
+
+    /// The next entry of `ALL`, after the last the first.
+    pub fn next(&self) -> Easing {
+        let index = Easing::ALL
+            .iter()
+            .position(|easing| easing == self)
+            .unwrap_or_default();
+        Easing::ALL[(index + 1) % Easing::ALL.len()]
+    }
+
+    /// The name the settings menu shows.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Easing::Linear => "linear",
+            Easing::CubicOut => "cubic out",
+            Easing::CubicInOut => "cubic in out",
+            Easing::BackOut => "back out",
+            Easing::ElasticOut => "elastic out",
+            Easing::Spring => "spring",
+        }
+    }
+
+    /// Maps linear progress `t` in `[0, 1]` onto the curve. Every curve
+    /// starts at 0 and ends at 1, overshooting curves may leave that range
+    /// in between.
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::CubicOut => 1.0 - (1.0 - t).powi(3),
+            Easing::CubicInOut => {
+                if t < 0.5 {
+                    4.0 * t.powi(3)
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+            Easing::BackOut => {
+                let c1 = 1.70158;
+                let c3 = c1 + 1.0;
+                1.0 + c3 * (t - 1.0).powi(3) + c1 * (t - 1.0).powi(2)
+            }
+            Easing::ElasticOut => {
+                if t == 0.0 || t == 1.0 {
+                    t
+                } else {
+                    2f32.powf(-10.0 * t) * ((t * 10.0 - 0.75) * (2.0 * PI / 3.0)).sin() + 1.0
+                }
+            }
+            Easing::Spring => {
+                if t == 1.0 {
+                    t
+                } else {
+                    1.0 - (-6.0 * t).exp() * (4.0 * PI * t).cos()
+                }
+            }
+        }
+    }
+}
+
+/// Duration and curve of a single kind of animation.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Tween {
+    pub duration: f32,
+    pub easing: Easing,
+}
+
+impl Tween {
+    pub const fn new(duration: f32, easing: Easing) -> Self {
+        Tween { duration, easing }
+    }
+}
+
+/// The kinds of animation that have a tween of their own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TweenKind {
+    Slide,
+    Merge,
+    Spawn,
+}
+
+impl TweenKind {
+    pub const ALL: [TweenKind; 3] = [TweenKind::Slide, TweenKind::Merge, TweenKind::Spawn];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TweenKind::Slide => "slide",
+            TweenKind::Merge => "merge",
+            TweenKind::Spawn => "spawn",
+        }
+    }
+}
+
+/// The tweens every `Animation` variant is played with. Merges first slide
+/// with the `slide` tween and then pop with the `merge` tween.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Tweens {
+    pub slide: Tween,
+    pub merge: Tween,
+    pub spawn: Tween,
+}
+
+impl Default for Tweens {
+    fn default() -> Self {
+        Tweens {
+            slide: Tween::new(0.1, Easing::CubicOut),
+            merge: Tween::new(0.1, Easing::BackOut),
+            spawn: Tween::new(0.1, Easing::BackOut),
+        }
+    }
+}
+
+impl Tweens {
+    pub fn get(&self, kind: TweenKind) -> Tween {
+        match kind {
+            TweenKind::Slide => self.slide,
+            TweenKind::Merge => self.merge,
+            TweenKind::Spawn => self.spawn,
+        }
+    }
+
+    pub fn get_mut(&mut self, kind: TweenKind) -> &mut Tween {
+        match kind {
+            TweenKind::Slide => &mut self.slide,
+            TweenKind::Merge => &mut self.merge,
+            TweenKind::Spawn => &mut self.spawn,
+        }
+    }
+}
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct AnimationConfig {
+    /// The tweens from the settings, `played` adjusts them to `motion`.
+    pub tweens: Tweens,
+    pub motion: Motion,
+    /// Global multiplier, 2.0 plays everything twice as fast.
+    pub speed: f32,
+}
+
+impl Default for AnimationConfig {
+    fn default() -> Self {
+        AnimationConfig {
+            tweens: Tweens::default(),
+            motion: Motion::Full,
+            speed: 1.0,
+        }
+    }
+}
+
+impl AnimationConfig {
+    /// The tweens animations are played with.
+    pub fn played(&self) -> Tweens {
+        self.motion.tweens(&self.tweens)
+    }
+
+    /// Seconds `animation` takes with the current speed.
+    pub fn duration(&self, animation: &Animation) -> f32 {
+        let tweens = self.played();
+        let seconds = match animation {
+            Animation::Sliding { .. } => tweens.slide.duration,
+            Animation::Merging { .. } => tweens.slide.duration + tweens.merge.duration,
+            Animation::Spawning { .. } => tweens.spawn.duration,
+        };
+        if self.speed > 0.0 {
+            seconds / self.speed
+        } else {
+            0.0
+        }
+    }
+
+    /// Splits the linear progress of a merge into its slide and pop phase.
+    fn merge_phases(&self, t: f32) -> (f32, f32) {
+        let Tweens { slide, merge, .. } = self.played();
+        let total = slide.duration + merge.duration;
+        if total <= 0.0 {
+            return (1.0, 1.0);
+        }
+        let split = slide.duration / total;
+        let slide = if split > 0.0 { t / split } else { 1.0 };
+        let pop = if split < 1.0 {
+            (t - split) / (1.0 - split)
+        } else {
+            1.0
+        };
+        (slide.clamp(0.0, 1.0), pop.clamp(0.0, 1.0))
+    }
+}
+
+#[derive(Component, Debug, Deref, DerefMut)]
+pub struct Animating {
+    #[deref]
+    pub timer: Timer,
+    pub animation: Animation,
+}
+
+impl Animating {
+    pub fn new(config: &AnimationConfig, animation: Animation) -> Self {
+        let duration = config.duration(&animation);
+        Animating {
+            timer: Timer::from_seconds(duration, TimerMode::Once),
+            animation,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Animation {
+    /// `wrap` is how far past `to` a tile that goes over the edge of a
+    /// wrapping board travels, zero for every other tile.
+    Sliding {
+        entity: Entity,
+        tile: Tile,
+        to: Position,
+        wrap: Vec2,
+    },
+    Merging {
+        entity1: Entity,
+        entity2: Entity,
+        tile1: Tile,
+        tile2: Tile,
+        to: Tile,
+        wrap1: Vec2,
+        wrap2: Vec2,
+    },
+    Spawning {
+        entity: Entity,
+        tile: Tile,
+    },
+}
+
+/// The point `p` of the way from `from` to `to + wrap`. A tile that goes
+/// over the edge comes back in on the other side once it is half a cell
+/// past it.
+fn wrapped_lerp(from: Vec2, to: Vec2, wrap: Vec2, p: f32) -> Vec2 {
+    let position = from.lerp(to + wrap, p);
+    if wrap == Vec2::ZERO {
+        return position;
+    }
+    let span = wrap.length();
+    let half = (TILE_SIZE + TILE_GAP) / 2.0;
+    if wrap.x != 0.0 {
+        Vec2::new((position.x + half).rem_euclid(span) - half, position.y)
+    } else {
+        // rows go down the screen
+        Vec2::new(position.x, half - (half - position.y).rem_euclid(span))
+    }
+}
+
+/// Translation, scale and transparency of `entity` at linear progress `t`.
+fn sample(
+    config: &AnimationConfig,
+    entity: Entity,
+    animation: &Animation,
+    t: f32,
+    z: f32,
+) -> (Vec3, f32, f32) {
+    match animation {
+        Animation::Sliding { tile, to, wrap, .. } => {
+            let from = to_screen(&tile.position);
+            let to = to_screen(to);
+            let p = config.played().slide.easing.apply(t);
+            (wrapped_lerp(from, to, *wrap, p).extend(z), 1.0, 0.0)
+        }
+        Animation::Merging {
+            entity1,
+            tile1,
+            tile2,
+            to,
+            wrap1,
+            wrap2,
+            ..
+        } => {
+            let is_target = entity == *entity1;
+            let (from, wrap) = if is_target {
+                (to_screen(&tile1.position), *wrap1)
+            } else {
+                (to_screen(&tile2.position), *wrap2)
+            };
+            let (slide, pop) = config.merge_phases(t);
+            let p = config.played().slide.easing.apply(slide);
+            let translation = wrapped_lerp(from, to_screen(&to.position), wrap, p).extend(z);
+            if is_target {
+                let scale =
+                    1.0 + MERGE_POP_STRENGTH * (config.played().merge.easing.apply(pop) - pop);
+                (translation, scale, 0.0)
+            } else {
+                // the other tile fades out underneath once both have arrived
+                (translation, 1.0, pop)
+            }
+        }
+        Animation::Spawning { tile, .. } => (
+            to_screen(&tile.position).extend(z),
+            config.played().spawn.easing.apply(t),
+            1.0 - t,
+        ),
+    }
+}
+
+pub type AnimatingTile = (
+    Entity,
+    &'static mut Position,
+    &'static mut Value,
+    &'static mut Transform,
+    &'static mut Transparency,
+    &'static mut Animating,
+    &'static TextId,
+);
+
+/// Puts a tile into the state its animation ends in. The entity lookup in
+/// `GameState` is already up to date, only components are touched here.
+#[allow(clippy::too_many_arguments)]
+pub fn finish_animation(
+    commands: &mut Commands,
+    entity: Entity,
+    animation: &Animation,
+    position: &mut Position,
+    value: &mut Value,
+    transform: &mut Transform,
+    transparency: &mut Transparency,
+    texts: &mut Query<&mut Text, With<TextMarker>>,
+    text_id: &TextId,
+) {
+    commands.entity(entity).remove::<Animating>();
+    transparency.0 = 0.0;
+    match animation {
+        Animation::Sliding { to, .. } => {
+            transform.translation = to_screen(to).extend(transform.translation.z);
+            *position = *to;
+        }
+        Animation::Merging { entity1, to, .. } => {
+            if entity == *entity1 {
+                transform.translation = to_screen(&to.position).extend(transform.translation.z);
+                transform.scale = Vec3::ONE;
+                *position = to.position;
+                *value = to.value;
+                if let Ok(mut text) = texts.get_mut(text_id.0) {
+                    text.sections[0].value = to.value.text_value();
+                }
+            } else {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        Animation::Spawning { .. } => {
+            transform.scale = Vec3::ONE;
+        }
+    }
+}
+
+pub fn update_animations(
+    mut commands: Commands,
+    time: Res<Time>,
+    config: Res<AnimationConfig>,
+    mut query: Query<AnimatingTile>,
+    mut texts: Query<&mut Text, With<TextMarker>>,
+) {
+    for (
+        entity,
+        mut position,
+        mut value,
+        mut transform,
+        mut transparency,
+        mut animating,
+        text_id,
+    ) in query.iter_mut()
+    {
+        animating.timer.tick(time.delta());
+        let t = animating.timer.fraction();
+        let (translation, scale, fade) = sample(
+            &config,
+            entity,
+            &animating.animation,
+            t,
+            transform.translation.z,
+        );
+        transform.translation = translation;
+        transform.scale = Vec3::splat(scale);
+        if transparency.0 != fade {
+            transparency.0 = fade;
+        }
+
+        // the merged tile counts its number up while the animation plays
+        if let Animation::Merging {
+            entity1, tile1, to, ..
+        } = &animating.animation
+        {
+            if let (true, Value::Number(from), Value::Number(to)) =
+                (entity == *entity1, tile1.value, to.value)
+            {
+                if let Ok(mut text) = texts.get_mut(text_id.0) {
+                    text.sections[0].value = score::count(from, to, t).to_string();
+                }
+            }
+        }
+
+        if animating.timer.finished() {
+            finish_animation(
+                &mut commands,
+                entity,
+                &animating.animation,
+                &mut position,
+                &mut value,
+                &mut transform,
+                &mut transparency,
+                &mut texts,
+                text_id,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_easing_endpoints() {
+        for easing in Easing::ALL.iter() {
+            assert!(easing.apply(0.0).abs() < 1e-6, "{:?} at 0", easing);
+            assert!((easing.apply(1.0) - 1.0).abs() < 1e-6, "{:?} at 1", easing);
+        }
+    }
+
+    #[test]
+    fn test_easing_shapes() {
+        assert!(Easing::CubicOut.apply(0.5) > 0.5);
+        assert!(Easing::CubicInOut.apply(0.25) < 0.25);
+        assert!((Easing::CubicInOut.apply(0.5) - 0.5).abs() < 1e-6);
+        let overshoots = |easing: Easing| (1..100).any(|i| easing.apply(i as f32 / 100.0) > 1.0);
+        assert!(overshoots(Easing::BackOut));
+        assert!(overshoots(Easing::ElasticOut));
+        assert!(overshoots(Easing::Spring));
+        assert!(!overshoots(Easing::CubicOut));
+    }
+
+    #[test]
+    fn test_curves_cycle() {
+        let mut easing = Easing::Linear;
+        for expected in Easing::ALL.iter().skip(1) {
+            easing = easing.next();
+            assert_eq!(easing, *expected);
+        }
+        assert_eq!(easing.next(), Easing::Linear);
+    }
+
+    #[test]
+    fn test_durations_follow_speed() {
+        let tile = Tile {
+            value: Value::Number(2),
+            position: Position { row: 0, col: 0 },
+        };
+        let entity = Entity::from_raw(0);
+        let merging = Animation::Merging {
+            entity1: entity,
+            entity2: entity,
+            tile1: tile,
+            tile2: tile,
+            to: tile,
+            wrap1: Vec2::ZERO,
+            wrap2: Vec2::ZERO,
+        };
+        let mut config = AnimationConfig::default();
+        assert!((config.duration(&merging) - 0.2).abs() < 1e-6);
+        config.speed = 2.0;
+        assert!((config.duration(&Animation::Spawning { entity, tile }) - 0.05).abs() < 1e-6);
+        config.speed = 0.0;
+        assert_eq!(config.duration(&merging), 0.0);
+    }
+
+    #[test]
+    fn test_fades() {
+        let config = AnimationConfig::default();
+        let tile = Tile {
+            value: Value::Number(2),
+            position: Position { row: 0, col: 0 },
+        };
+        let (e1, e2) = (Entity::from_raw(1), Entity::from_raw(2));
+        let spawning = Animation::Spawning { entity: e1, tile };
+        assert_eq!(sample(&config, e1, &spawning, 0.0, 0.0).2, 1.0);
+        assert_eq!(sample(&config, e1, &spawning, 1.0, 0.0).2, 0.0);
+
+        let merging = Animation::Merging {
+            entity1: e1,
+            entity2: e2,
+            tile1: tile,
+            tile2: tile,
+            to: tile,
+            wrap1: Vec2::ZERO,
+            wrap2: Vec2::ZERO,
+        };
+        // the target stays opaque, the other tile fades during the pop
+        assert_eq!(sample(&config, e1, &merging, 0.75, 0.0).2, 0.0);
+        assert_eq!(sample(&config, e2, &merging, 0.25, 0.0).2, 0.0);
+        assert_eq!(sample(&config, e2, &merging, 0.75, 0.0).2, 0.5);
+    }
+
+    #[test]
+    fn test_wrapped_tiles_come_back_in_on_the_other_side() {
+        let (from, to) = (Vec2::ZERO, Vec2::new(360.0, 0.0));
+        // left over the edge of a 4x4 board, from the first to the last column
+        let wrap = Vec2::new(-480.0, 0.0);
+        assert_eq!(wrapped_lerp(from, to, wrap, 0.25), Vec2::new(-30.0, 0.0));
+        assert_eq!(wrapped_lerp(from, to, wrap, 0.75), Vec2::new(390.0, 0.0));
+        assert_eq!(wrapped_lerp(from, to, wrap, 1.0), to);
+        assert_eq!(
+            wrapped_lerp(from, to, Vec2::ZERO, 0.5),
+            Vec2::new(180.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_merge_phases() {
+        let config = AnimationConfig::default();
+        assert_eq!(config.merge_phases(0.25), (0.5, 0.0));
+        assert_eq!(config.merge_phases(0.75), (1.0, 0.5));
+        let instant = AnimationConfig {
+            tweens: Tweens {
+                slide: Tween::new(0.0, Easing::Linear),
+                merge: Tween::new(0.0, Easing::Linear),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(instant.merge_phases(0.0), (1.0, 1.0));
+    }
+}
@@ -6,8 +6,9 @@ use std::str::FromStr;
 
 use crate::action::Action;
 use crate::direction::Direction;
+use crate::merge::MergeRule;
 use crate::tile::position::{LineTraversals, Position};
-use crate::tile::value::{Value, MAX_TILE_VALUE};
+use crate::tile::value::Value;
 use crate::tile::Tile;
 
 #[derive(Debug, Clone)]
@@ -15,6 +16,11 @@ pub struct Board {
     pub size: usize,
     pub tiles: HashMap<Position, Value>,
     pub traversal_map: HashMap<Direction, LineTraversals>,
+    /// Tiles leave over one edge and come back in on the opposite one, so
+    /// every row and column is a ring.
+    pub wrap: bool,
+    /// Which tiles merge and which values new tiles get.
+    pub rule: MergeRule,
 }
 
 impl Board {
@@ -30,9 +36,21 @@ impl Board {
             size,
             tiles,
             traversal_map: Position::generate_traversal_map(size),
+            wrap: false,
+            rule: MergeRule::Classic,
         }
     }
 
+    pub fn wrapping(mut self, wrap: bool) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    pub fn merging(mut self, rule: MergeRule) -> Self {
+        self.rule = rule;
+        self
+    }
+
     pub fn set_value(&mut self, p: &Position, value: Value) {
         self.tiles.insert(p.clone(), value);
     }
@@ -64,7 +82,8 @@ impl Board {
         let has_empty_positions = !empty_positions.is_empty();
         if has_empty_positions {
             let rand_position = empty_positions[rng.gen_range(0..empty_positions.len())];
-            let rand_value = if rng.gen_bool(0.9) { 2 } else { 4 };
+            let (common, rare, p) = self.rule.spawns();
+            let rand_value = if rng.gen_bool(p) { common } else { rare };
             let rand_tile = Value::Number(rand_value);
 
             Some(Action::SpawnRandomTile(Tile {
@@ -90,6 +109,12 @@ impl Board {
                 self.set_value(&tile2.position, Value::Empty);
                 self.set_value(&to, value);
             }
+            Action::MergeThreeTiles(tile1, tile2, tile3, to, value) => {
+                self.set_value(&tile1.position, Value::Empty);
+                self.set_value(&tile2.position, Value::Empty);
+                self.set_value(&tile3.position, Value::Empty);
+                self.set_value(&to, value);
+            }
         }
     }
 
@@ -105,7 +130,67 @@ impl Board {
         events
     }
 
-    pub fn slide_and_merge_line(&self, line_traversal: &Vec<Position>) -> Vec<Action> {
+    /// Walls split a line into segments that slide and merge on their own.
+    /// On a wrapping board the line is a ring and first cut open, see
+    /// `ring_start`.
+    pub fn slide_and_merge_line(&self, line_traversal: &[Position]) -> Vec<Action> {
+        let mut line = line_traversal.to_vec();
+        if self.wrap {
+            line.rotate_left(self.ring_start(line_traversal));
+        }
+        line.split(|position| self.get_value(position) == Value::Wall)
+            .flat_map(|segment| match self.rule.group() {
+                3 => self.slide_and_merge_triples(segment),
+                _ => self.slide_and_merge_segment(segment),
+            })
+            .collect()
+    }
+
+    /// Where a ring starts once it is cut open, tiles slide towards the
+    /// start. A ring with walls is cut behind its last one. A ring with a
+    /// free cell at the front edge starts there and slides as usual.
+    /// Otherwise it is cut after its last empty cell, so the tiles packed
+    /// against the front edge stay put and only go over it once the tiles
+    /// behind the other edge make room. A full ring is cut in front of the
+    /// first tile that merges with the ones after it.
+    fn ring_start(&self, ring: &[Position]) -> usize {
+        let values: Vec<Value> = ring.iter().map(|pos| self.get_value(pos)).collect();
+        if let Some(last) = values.iter().rposition(|value| *value == Value::Wall) {
+            return (last + 1) % ring.len();
+        }
+        if values[0] == Value::Empty {
+            return 0;
+        }
+        if let Some(last) = values.iter().rposition(|value| *value == Value::Empty) {
+            return last + 1;
+        }
+        let group = self.rule.group();
+        (0..values.len())
+            .find(|i| {
+                let numbers: Vec<u32> = (0..group)
+                    .filter_map(|k| match values[(i + k) % values.len()] {
+                        Value::Number(n) => Some(n),
+                        Value::Empty | Value::Wall => None,
+                    })
+                    .collect();
+                self.rule.merges(&numbers)
+            })
+            .unwrap_or(0)
+    }
+
+    /// Whether a tile that moved from `from` to `to` in `direction` went over
+    /// the edge, which only happens on wrapping boards.
+    pub fn crosses_edge(&self, from: &Position, to: &Position, direction: &Direction) -> bool {
+        self.wrap
+            && match direction {
+                Direction::Left => to.col > from.col,
+                Direction::Right => to.col < from.col,
+                Direction::Up => to.row > from.row,
+                Direction::Down => to.row < from.row,
+            }
+    }
+
+    fn slide_and_merge_segment(&self, line_traversal: &[Position]) -> Vec<Action> {
         let mut events = vec![];
         let mut board_clone = self.clone();
 
@@ -126,7 +211,7 @@ impl Board {
                 };
 
             if let Some((prev_idx, prev_value)) = prev {
-                let can_merge = prev_value == current_value && prev_value < MAX_TILE_VALUE;
+                let can_merge = self.rule.merges(&[prev_value, current_value]);
                 let prev_cell = line_traversal[prev_idx];
 
                 if can_merge {
@@ -176,6 +261,106 @@ impl Board {
         return events;
     }
 
+    /// `slide_and_merge_segment` for rules that merge three tiles at once.
+    /// Tiles are taken from the front, three that merge become one tile and
+    /// any other tile slides up behind the one before it.
+    fn slide_and_merge_triples(&self, segment: &[Position]) -> Vec<Action> {
+        let tiles: Vec<Tile> = segment
+            .iter()
+            .map(|position| self.get_tile(position))
+            .filter(|tile| matches!(tile.value, Value::Number(_)))
+            .collect();
+        let mut events = vec![];
+        let mut rest = &tiles[..];
+        for to in segment {
+            let Some(first) = rest.first() else {
+                break;
+            };
+            let numbers: Vec<u32> = rest
+                .iter()
+                .take(3)
+                .filter_map(|tile| match tile.value {
+                    Value::Number(n) => Some(n),
+                    Value::Empty | Value::Wall => None,
+                })
+                .collect();
+            if self.rule.merges(&numbers) {
+                let value = rest[..3]
+                    .iter()
+                    .fold(Value::Empty, |value, tile| value.merge(tile.value));
+                events.push(Action::MergeThreeTiles(
+                    rest[0], rest[1], rest[2], *to, value,
+                ));
+                rest = &rest[3..];
+            } else {
+                if first.position != *to {
+                    events.push(Action::SlideTile(*first, *to));
+                }
+                rest = &rest[1..];
+            }
+        }
+        events
+    }
+
+    /// True as long as at least one direction still moves a tile. On a
+    /// wrapping board tiles at opposite edges merge too.
+    pub fn can_move(&self) -> bool {
+        [
+            Direction::Left,
+            Direction::Right,
+            Direction::Up,
+            Direction::Down,
+        ]
+        .iter()
+        .any(|direction| !self.plan_slide_and_merge(direction).is_empty())
+    }
+
+    /// The largest value on the board, 0 on an empty board.
+    pub fn max_tile(&self) -> u32 {
+        self.tiles
+            .values()
+            .map(|value| match value {
+                Value::Number(n) => *n,
+                Value::Empty | Value::Wall => 0,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The move that gains the most points while keeping the most cells
+    /// free. Only looks a single move ahead, ties go to the earlier
+    /// direction in left, up, right, down order.
+    pub fn hint(&self) -> Option<Direction> {
+        let mut best: Option<(u32, Direction)> = None;
+        for direction in [
+            Direction::Left,
+            Direction::Up,
+            Direction::Right,
+            Direction::Down,
+        ] {
+            let actions = self.plan_slide_and_merge(&direction);
+            if actions.is_empty() {
+                continue;
+            }
+            let mut board = self.clone();
+            let mut gained = 0;
+            for action in actions {
+                gained += action.score();
+                board.apply(action);
+            }
+            let empty = board
+                .tiles
+                .values()
+                .filter(|value| **value == Value::Empty)
+                .count() as u32;
+            let rating = gained + 4 * empty;
+            if best.is_none_or(|(best, _)| rating > best) {
+                best = Some((rating, direction));
+            }
+        }
+        best.map(|(_, direction)| direction)
+    }
+
     pub fn slide_and_merge(&mut self, direction: Direction) -> bool {
         let events = self.plan_slide_and_merge(&direction);
         let moved = !events.is_empty();
@@ -196,32 +381,44 @@ impl fmt::Display for Board {
             .iter()
             .for_each(|line| {
                 line.iter().for_each(|pos| {
-                    write!(f, "{}", self.get_value(pos)).unwrap();
+                    write!(f, "{}", self.rule.notation(&self.get_value(pos))).unwrap();
                 });
             });
         Ok(())
     }
 }
 
-impl FromStr for Board {
-    type Err = ();
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.len() != 16 {
+impl Board {
+    /// One character per cell, row by row, in the notation of `rule`. The
+    /// length sets the board size, 16 characters make a 4x4 board.
+    pub fn parse_with(s: &str, rule: MergeRule) -> Result<Board, ()> {
+        let size = (1..=s.len())
+            .find(|size| size * size >= s.len())
+            .ok_or(())?;
+        if size * size != s.len() {
             return Err(());
         }
 
-        let mut board = Board::new(4);
+        let mut board = Board::new(size).merging(rule);
         for (i, hex_char) in s.chars().enumerate() {
-            let row = i / 4;
-            let col = i % 4;
+            let row = i / size;
+            let col = i % size;
             let pos = &Position { row, col };
-            board.set_value(pos, Value::from_str(&hex_char.to_string()).map_err(|_| ())?);
+            board.set_value(pos, rule.parse(&hex_char.to_string()).ok_or(())?);
         }
         Ok(board)
     }
 }
 
+impl FromStr for Board {
+    type Err = ();
+
+    /// A board with classic rules, see `parse_with`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Board::parse_with(s, MergeRule::Classic)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use bevy::reflect::Reflect;
@@ -404,6 +601,170 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_can_move() {
+        assert!(board_from_str("1000000000000000").can_move());
+        assert!(board_from_str("1234234134124113").can_move());
+        assert!(!board_from_str("1234234134124123").can_move());
+        assert!(!board_from_str("0000000000000000").can_move());
+    }
+
+    #[test]
+    fn test_walls_split_lines() {
+        let mut board = board_from_str("01X111X1X0000000");
+        assert!(board.slide_and_merge(Direction::Left));
+        assert_eq!(board.to_string(), "10X120X1X0000000");
+        let mut board = board_from_str("0000X00000001000");
+        assert!(board.slide_and_merge(Direction::Up));
+        assert_eq!(board.to_string(), "0000X00010000000");
+        // tiles never pass a wall, so a board can be stuck with empty cells
+        assert!(!board_from_str("1XXXXXXXXXXXXXX0").can_move());
+        assert_eq!(board_from_str("XXXX000000000000").max_tile(), 0);
+    }
+
+    #[test]
+    fn test_wrapping_lines_are_rings() {
+        let wrapping = |s: &str| board_from_str(s).wrapping(true);
+        let cases = [
+            // packed against the front edge, nothing moves
+            ("1000000000000000", Direction::Left, "1000000000000000", 0),
+            ("1200000000000000", Direction::Left, "1200000000000000", 0),
+            // packed across the edge
+            ("2004000000000000", Direction::Left, "2004000000000000", 0),
+            // a free front edge slides as usual
+            ("0011000000000000", Direction::Left, "2000000000000000", 1),
+            // the front tile goes over the edge once the merge makes room
+            ("4022000000000000", Direction::Left, "0034000000000000", 2),
+            ("1001000000000000", Direction::Left, "0002000000000000", 1),
+            ("1000000000001000", Direction::Up, "0000000000002000", 1),
+            ("1221000000000000", Direction::Left, "0320000000000000", 2),
+            ("1221000000000000", Direction::Right, "0230000000000000", 2),
+            ("1X01000000000000", Direction::Left, "0X20000000000000", 1),
+        ];
+        for (in_board, direction, out_board, n_events) in cases {
+            let mut board = wrapping(in_board);
+            assert_eq!(
+                board.plan_slide_and_merge(&direction).len(),
+                n_events,
+                "{} --{}--> events",
+                in_board,
+                direction
+            );
+            board.slide_and_merge(direction);
+            assert_eq!(
+                board.to_string(),
+                out_board,
+                "{} --{}-->",
+                in_board,
+                direction
+            );
+        }
+        // tiles at opposite edges are neighbours
+        assert!(wrapping("1234234134121231").can_move());
+        assert!(!board_from_str("1234234134121231").can_move());
+        assert!(!wrapping("1234234134124123").can_move());
+    }
+
+    #[test]
+    fn test_wrapped_tiles_cross_the_edge() {
+        let board = board_from_str("4022000000000000").wrapping(true);
+        let actions = board.plan_slide_and_merge(&Direction::Left);
+        let crossed: Vec<Position> = actions
+            .iter()
+            .filter_map(|action| match action {
+                Action::SlideTile(tile, to)
+                    if board.crosses_edge(&tile.position, to, &Direction::Left) =>
+                {
+                    Some(tile.position)
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(crossed, vec![Position { row: 0, col: 0 }]);
+        assert!(!board_from_str("0220000000000000").crosses_edge(
+            &Position { row: 0, col: 0 },
+            &Position { row: 0, col: 3 },
+            &Direction::Left
+        ));
+    }
+
+    #[test]
+    fn test_merge_rules() {
+        let cases = [
+            (MergeRule::Fibonacci, "1123000000000000", "2400000000000000"),
+            (MergeRule::Fibonacci, "0222000000000000", "2220000000000000"),
+            (MergeRule::Fibonacci, "AB00000000000000", "AB00000000000000"),
+            (MergeRule::Threes, "1122000000000000", "1320000000000000"),
+            (MergeRule::Threes, "1233000000000000", "3400000000000000"),
+            (MergeRule::Triples, "1110000000000000", "2000000000000000"),
+            (MergeRule::Triples, "1111000000000000", "2100000000000000"),
+            (MergeRule::Triples, "2111000000000000", "2200000000000000"),
+            (MergeRule::Triples, "1011000000000000", "2000000000000000"),
+            (MergeRule::Triples, "X111000000000000", "X200000000000000"),
+        ];
+        for (rule, in_board, out_board) in cases {
+            let mut board = Board::parse_with(in_board, rule).unwrap();
+            board.slide_and_merge(Direction::Left);
+            assert_eq!(board.to_string(), out_board, "{} {} -->", rule, in_board);
+        }
+        let triples = Board::parse_with("0111000000000000", MergeRule::Triples).unwrap();
+        match triples.plan_slide_and_merge(&Direction::Right).as_slice() {
+            [Action::MergeThreeTiles(tile1, _, tile3, to, value)] => {
+                assert_eq!(tile1.position, Position { row: 0, col: 3 });
+                assert_eq!(tile3.position, Position { row: 0, col: 1 });
+                assert_eq!(*to, Position { row: 0, col: 3 });
+                assert_eq!(*value, Value::Number(3));
+            }
+            actions => panic!("expected a triple merge, got {:?}", actions),
+        }
+        // a full ring is cut in front of three tiles that merge
+        let board = Board::parse_with("1000100020001000", MergeRule::Triples).unwrap();
+        assert!(!board.clone().slide_and_merge(Direction::Up));
+        let mut board = board.wrapping(true);
+        assert!(board.slide_and_merge(Direction::Up));
+        assert_eq!(board.to_string(), "2000000000002000");
+    }
+
+    #[test]
+    fn test_rules_pick_their_spawns() {
+        for rule in MergeRule::ALL {
+            let (common, rare, _) = rule.spawns();
+            let board = Board::new(4).merging(rule);
+            let mut rng = ChaCha8Rng::seed_from_u64(7);
+            for _ in 0..20 {
+                let Some(Action::SpawnRandomTile(tile)) = board.plan_spawn_random_tile(&mut rng)
+                else {
+                    panic!("nothing spawned");
+                };
+                assert!(
+                    [Value::Number(common), Value::Number(rare)].contains(&tile.value),
+                    "{} spawned {:?}",
+                    rule,
+                    tile.value
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_hint() {
+        assert_eq!(
+            board_from_str("0000000000001122").hint(),
+            Some(Direction::Left)
+        );
+        assert_eq!(
+            board_from_str("1000100000000000").hint(),
+            Some(Direction::Up)
+        );
+        assert_eq!(board_from_str("1234234134124123").hint(), None);
+    }
+
+    #[test]
+    fn test_max_tile() {
+        assert_eq!(board_from_str("0000000000000000").max_tile(), 0);
+        assert_eq!(board_from_str("1000000A00000300").max_tile(), 1024);
+    }
+
     #[test]
     fn test_board_serialization() {
         let board = board_from_str("123456789A000000");
@@ -0,0 +1,328 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use bevy::prelude::Entity;
+use bevy::utils::HashMap;
+
+use crate::action::Action;
+use crate::board::Board;
+use crate::direction::Direction;
+use crate::tile::position::Position;
+use crate::tile::value::Value;
+
+pub const RECENT_ACTIONS_LEN: usize = 16;
+
+/// A tile entity as seen by the checker.
+pub type TileView = (Entity, Position, Value);
+
+/// A single disagreement between the logical `Board` and the tile entities.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Inconsistency {
+    MissingEntity {
+        position: Position,
+        value: Value,
+    },
+    DuplicateEntities {
+        position: Position,
+        entities: Vec<Entity>,
+    },
+    ValueMismatch {
+        position: Position,
+        entity: Entity,
+        board: Value,
+        entity_value: Value,
+    },
+    OrphanedEntity {
+        position: Position,
+        entity: Entity,
+        value: Value,
+    },
+    StaleMapping {
+        position: Position,
+        mapped: Option<Entity>,
+        actual: Option<Entity>,
+    },
+}
+
+impl fmt::Display for Inconsistency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Inconsistency::MissingEntity { position, value } => {
+                write!(f, "board has {} at {} but no tile entity", value, position)
+            }
+            Inconsistency::DuplicateEntities { position, entities } => {
+                write!(
+                    f,
+                    "{} tile entities at {}: {:?}",
+                    entities.len(),
+                    position,
+                    entities
+                )
+            }
+            Inconsistency::ValueMismatch {
+                position,
+                entity,
+                board,
+                entity_value,
+            } => write!(
+                f,
+                "board has {} at {} but {:?} has {}",
+                board, position, entity, entity_value
+            ),
+            Inconsistency::OrphanedEntity {
+                position,
+                entity,
+                value,
+            } => write!(
+                f,
+                "{:?} with {} at {} has no counterpart on the board",
+                entity, value, position
+            ),
+            Inconsistency::StaleMapping {
+                position,
+                mapped,
+                actual,
+            } => write!(
+                f,
+                "entity map points {} to {:?} but the tile there is {:?}",
+                position, mapped, actual
+            ),
+        }
+    }
+}
+
+/// Compares the board against the tile entities (`tiles`) and against the
+/// position to entity lookup the game keeps (`entities`).
+pub fn check(
+    board: &Board,
+    entities: &HashMap<Position, Entity>,
+    tiles: &[TileView],
+) -> Vec<Inconsistency> {
+    let mut issues = vec![];
+    let mut by_position: HashMap<Position, Vec<(Entity, Value)>> = HashMap::new();
+    for (entity, position, value) in tiles.iter() {
+        by_position
+            .entry(*position)
+            .or_default()
+            .push((*entity, *value));
+    }
+
+    for line in board.traversal_map.get(&Direction::Left).unwrap() {
+        for position in line {
+            let value = board.get_value(position);
+            let found = by_position.remove(position).unwrap_or_default();
+            let actual = match found.as_slice() {
+                [] => {
+                    if value != Value::Empty {
+                        issues.push(Inconsistency::MissingEntity {
+                            position: *position,
+                            value,
+                        });
+                    }
+                    None
+                }
+                [(entity, entity_value)] => {
+                    if value == Value::Empty {
+                        issues.push(Inconsistency::OrphanedEntity {
+                            position: *position,
+                            entity: *entity,
+                            value: *entity_value,
+                        });
+                    } else if value != *entity_value {
+                        issues.push(Inconsistency::ValueMismatch {
+                            position: *position,
+                            entity: *entity,
+                            board: value,
+                            entity_value: *entity_value,
+                        });
+                    }
+                    Some(*entity)
+                }
+                _ => {
+                    issues.push(Inconsistency::DuplicateEntities {
+                        position: *position,
+                        entities: found.iter().map(|(entity, _)| *entity).collect(),
+                    });
+                    None
+                }
+            };
+            let mapped = entities.get(position).copied();
+            if mapped != actual && !(actual.is_none() && found.len() > 1) {
+                issues.push(Inconsistency::StaleMapping {
+                    position: *position,
+                    mapped,
+                    actual,
+                });
+            }
+        }
+    }
+
+    // whatever is left sits outside of the board
+    for (position, found) in by_position.into_iter() {
+        for (entity, value) in found {
+            issues.push(Inconsistency::OrphanedEntity {
+                position,
+                entity,
+                value,
+            });
+        }
+    }
+    issues
+}
+
+/// Renders the board and the entity view side by side, one row per line.
+/// Cells without an entity are shown as `.`, cells with several as `*`.
+pub fn dump_views(board: &Board, tiles: &[TileView]) -> String {
+    let mut out = String::from("board  entities\n");
+    for row in 0..board.size {
+        for col in 0..board.size {
+            out.push_str(
+                &board
+                    .rule
+                    .notation(&board.get_value(&Position { row, col })),
+            );
+        }
+        out.push_str(&" ".repeat(7usize.saturating_sub(board.size)));
+        for col in 0..board.size {
+            let position = Position { row, col };
+            let found: Vec<_> = tiles.iter().filter(|(_, p, _)| *p == position).collect();
+            match found.as_slice() {
+                [] => out.push('.'),
+                [(_, _, value)] => out.push_str(&board.rule.notation(value)),
+                _ => out.push('*'),
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// The most recent actions that went through the renderer, kept so that a
+/// failed check can show how the views drifted apart.
+#[derive(Debug, Default)]
+pub struct ActionLog {
+    pub recent: VecDeque<Action>,
+    pub total: usize,
+}
+
+impl ActionLog {
+    pub fn record(&mut self, action: &Action) {
+        if self.recent.len() == RECENT_ACTIONS_LEN {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(action.clone());
+        self.total += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merge::MergeRule;
+
+    fn setup(s: &str) -> (Board, HashMap<Position, Entity>, Vec<TileView>) {
+        let board: Board = s.parse().unwrap();
+        let mut entities = HashMap::new();
+        let mut tiles = vec![];
+        for (i, (position, value)) in board.tiles.iter().enumerate() {
+            if *value != Value::Empty {
+                let entity = Entity::from_raw(i as u32);
+                entities.insert(*position, entity);
+                tiles.push((entity, *position, *value));
+            }
+        }
+        (board, entities, tiles)
+    }
+
+    #[test]
+    fn test_consistent_views() {
+        let (board, entities, tiles) = setup("3301100000000010");
+        assert_eq!(check(&board, &entities, &tiles), vec![]);
+    }
+
+    #[test]
+    fn test_missing_and_orphaned_entities() {
+        let (board, mut entities, mut tiles) = setup("1000000000000000");
+        let origin = Position { row: 0, col: 0 };
+        let other = Position { row: 0, col: 1 };
+        tiles[0].1 = other;
+        entities.remove(&origin);
+        entities.insert(other, tiles[0].0);
+
+        let issues = check(&board, &entities, &tiles);
+        assert!(issues.contains(&Inconsistency::MissingEntity {
+            position: origin,
+            value: Value::Number(2),
+        }));
+        assert!(issues.contains(&Inconsistency::OrphanedEntity {
+            position: other,
+            entity: tiles[0].0,
+            value: Value::Number(2),
+        }));
+    }
+
+    #[test]
+    fn test_duplicates_and_value_mismatch() {
+        let (board, entities, mut tiles) = setup("1200000000000000");
+        let position = Position { row: 0, col: 1 };
+        tiles.push((Entity::from_raw(99), position, Value::Number(4)));
+        tiles
+            .iter_mut()
+            .find(|(_, p, _)| *p == Position { row: 0, col: 0 })
+            .unwrap()
+            .2 = Value::Number(8);
+
+        let issues = check(&board, &entities, &tiles);
+        assert_eq!(issues.len(), 2, "{:?}", issues);
+        assert!(matches!(issues[0], Inconsistency::ValueMismatch { .. }));
+        assert!(matches!(issues[1], Inconsistency::DuplicateEntities { .. }));
+    }
+
+    #[test]
+    fn test_stale_mapping() {
+        let (board, mut entities, tiles) = setup("1000000000000000");
+        entities.insert(Position { row: 3, col: 3 }, Entity::from_raw(42));
+        let issues = check(&board, &entities, &tiles);
+        assert_eq!(
+            issues,
+            vec![Inconsistency::StaleMapping {
+                position: Position { row: 3, col: 3 },
+                mapped: Some(Entity::from_raw(42)),
+                actual: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_action_log_is_bounded() {
+        let mut log = ActionLog::default();
+        let (board, _, _) = setup("1000000000000000");
+        let tile = board.get_tile(&Position { row: 0, col: 0 });
+        for _ in 0..RECENT_ACTIONS_LEN + 3 {
+            log.record(&Action::SpawnRandomTile(tile));
+        }
+        assert_eq!(log.recent.len(), RECENT_ACTIONS_LEN);
+        assert_eq!(log.total, RECENT_ACTIONS_LEN + 3);
+    }
+
+    #[test]
+    fn test_dump_views() {
+        let (board, _, mut tiles) = setup("1000000000000002");
+        tiles.retain(|(_, _, value)| *value == Value::Number(2));
+        let dump = dump_views(&board, &tiles);
+        assert_eq!(
+            dump,
+            "board  entities\n1000   1...\n0000   ....\n0000   ....\n0002   ....\n"
+        );
+
+        // other merge rules are written in their own notation, a threes 3
+        // is on the third rung
+        let board = Board::parse_with("3120000000000000", MergeRule::Threes).unwrap();
+        let position = Position { row: 0, col: 0 };
+        let tiles = vec![(Entity::from_raw(0), position, board.get_value(&position))];
+        assert_eq!(
+            dump_views(&board, &tiles).lines().nth(1),
+            Some("3120   3...")
+        );
+    }
+}
@@ -0,0 +1,347 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::date::Date;
+use crate::direction::Direction;
+use crate::highscores::{fnv1a, CLASSIC_RULES};
+use crate::replay;
+use crate::settings::{settings_path, write_atomically};
+use crate::GameFinished;
+
+/// Lives next to the settings file.
+pub const DAILY_FILE: &str = "daily.ron";
+/// Everyone plays the daily challenge on the same board size.
+pub const DAILY_BOARD_SIZE: usize = 4;
+/// Starts every result string.
+const RESULT_PREFIX: &str = "2048daily";
+
+/// The seed of the challenge on `date` with `rules`. Days change at
+/// midnight UTC, so everyone gets the same seed on the same day.
+pub fn daily_seed(date: Date, rules: &str) -> u64 {
+    fnv1a(format!("{}|{}", date, rules).as_bytes())
+}
+
+/// The outcome of one daily challenge, shared as a string like
+/// `2048daily:2026-10-18:classic:<seed>:<score>:<max tile>:<moves>:<replay>`
+/// with the replay packed by `replay::pack`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct DailyResult {
+    pub date: Date,
+    pub rules: String,
+    pub seed: u64,
+    pub score: u32,
+    pub max_tile: u32,
+    pub moves: Vec<Direction>,
+}
+
+impl DailyResult {
+    pub(crate) fn new(date: Date, rules: &str, game: &GameFinished) -> DailyResult {
+        DailyResult {
+            date,
+            rules: rules.to_string(),
+            seed: game.seed,
+            score: game.score,
+            max_tile: game.max_tile,
+            moves: game.moves.clone(),
+        }
+    }
+
+    /// Checks that the seed belongs to the day and that the replay ends in
+    /// the score and largest tile of the result.
+    pub fn verify(&self) -> Result<(), String> {
+        if self.seed != daily_seed(self.date, &self.rules) {
+            return Err(format!(
+                "seed {} is not the one of {}",
+                self.seed, self.date
+            ));
+        }
+        let result = replay::simulate(DAILY_BOARD_SIZE, self.seed, &self.moves)
+            .ok_or_else(|| "the replay contains moves that are not possible".to_string())?;
+        if result.score != self.score || result.max_tile != self.max_tile {
+            return Err(format!(
+                "the replay ends with {} ({}), not {} ({})",
+                result.score, result.max_tile, self.score, self.max_tile
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for DailyResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}:{}:{}:{}:{}:{}",
+            RESULT_PREFIX,
+            self.date,
+            self.rules,
+            self.seed,
+            self.score,
+            self.max_tile,
+            self.moves.len(),
+            replay::pack(&self.moves)
+        )
+    }
+}
+
+impl FromStr for DailyResult {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |what: &str| format!("invalid daily result, bad {}", what);
+        let parts: Vec<&str> = s.trim().split(':').collect();
+        let [prefix, date, rules, seed, score, max_tile, count, moves] = parts[..] else {
+            return Err(invalid("format"));
+        };
+        if prefix != RESULT_PREFIX {
+            return Err(invalid("prefix"));
+        }
+        let moves = replay::unpack(moves).ok_or_else(|| invalid("replay"))?;
+        if count.parse::<usize>() != Ok(moves.len()) {
+            return Err(invalid("move count"));
+        }
+        Ok(DailyResult {
+            date: date.parse()?,
+            rules: rules.to_string(),
+            seed: seed.parse().map_err(|_| invalid("seed"))?,
+            score: score.parse().map_err(|_| invalid("score"))?,
+            max_tile: max_tile.parse().map_err(|_| invalid("max tile"))?,
+            moves,
+        })
+    }
+}
+
+impl TryFrom<String> for DailyResult {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<DailyResult> for String {
+    fn from(result: DailyResult) -> Self {
+        result.to_string()
+    }
+}
+
+/// The scored attempt of every day played so far.
+#[derive(Resource, Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DailyLog(pub Vec<DailyResult>);
+
+impl DailyLog {
+    pub fn parse(ron: &str) -> Result<DailyLog, String> {
+        ron::from_str(ron).map_err(|err| err.to_string())
+    }
+
+    pub fn to_ron(&self) -> String {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .expect("daily log serialize")
+    }
+
+    /// Loads `path`, starting with no days played when there is no file yet
+    /// or it cannot be read.
+    pub fn load_or_default(path: &Path) -> DailyLog {
+        match fs::read_to_string(path) {
+            Ok(ron) => DailyLog::parse(&ron).unwrap_or_else(|err| {
+                warn!("malformed daily log: {} ({})", err, path.display());
+                DailyLog::default()
+            }),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => DailyLog::default(),
+            Err(err) => {
+                warn!("could not read daily log: {} ({})", err, path.display());
+                DailyLog::default()
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        write_atomically(path, &self.to_ron())
+    }
+
+    pub fn get(&self, date: Date) -> Option<&DailyResult> {
+        self.0.iter().find(|result| result.date == date)
+    }
+
+    /// Keeps `result` unless its day already has one, only the first
+    /// attempt counts.
+    pub fn record(&mut self, result: DailyResult) -> bool {
+        if self.get(result.date).is_some() {
+            return false;
+        }
+        self.0.push(result);
+        true
+    }
+}
+
+/// Where the daily log lives, `None` when settings are not persisted.
+pub fn daily_path() -> Option<PathBuf> {
+    settings_path().map(|path| path.with_file_name(DAILY_FILE))
+}
+
+#[derive(Resource, Debug)]
+pub struct DailyPath(pub Option<PathBuf>);
+
+/// Prints today's seed and result for the `daily` command line subcommand.
+pub fn print_daily() {
+    let today = Date::today();
+    println!(
+        "daily challenge {}, seed {}",
+        today,
+        daily_seed(today, CLASSIC_RULES)
+    );
+    let log = daily_path()
+        .as_deref()
+        .map(DailyLog::load_or_default)
+        .unwrap_or_default();
+    match log.get(today) {
+        Some(result) => println!(
+            "you scored {} with a {} in {} moves, share this to show how:\n{}",
+            result.score,
+            result.max_tile,
+            result.moves.len(),
+            result
+        ),
+        None => println!("not played yet"),
+    }
+}
+
+/// Keeps the first finished daily challenge of every day.
+fn record_daily(
+    path: Res<DailyPath>,
+    mut log: ResMut<DailyLog>,
+    mut finished: EventReader<GameFinished>,
+) {
+    let mut changed = false;
+    for game in finished.read() {
+        let Some(date) = game.daily else {
+            continue;
+        };
+        let result = DailyResult::new(date, CLASSIC_RULES, game);
+        if log.record(result.clone()) {
+            println!("daily challenge result: {}", result);
+            changed = true;
+        }
+    }
+    if !changed {
+        return;
+    }
+    if let Some(path) = &path.0 {
+        if let Err(err) = log.save(path) {
+            warn!("could not write daily log: {} ({})", err, path.display());
+        }
+    }
+}
+
+pub struct DailyPlugin;
+
+impl Plugin for DailyPlugin {
+    fn build(&self, app: &mut App) {
+        let path = daily_path();
+        let log = path
+            .as_deref()
+            .map(DailyLog::load_or_default)
+            .unwrap_or_default();
+        app.insert_resource(log)
+            .insert_resource(DailyPath(path))
+            .add_systems(Last, record_daily);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merge::MergeRule;
+    use crate::mode::GameMode;
+
+    fn today() -> Date {
+        "2026-10-18".parse().unwrap()
+    }
+
+    /// A real daily challenge of `moves` moves.
+    fn played(date: Date, moves: usize) -> DailyResult {
+        let seed = daily_seed(date, CLASSIC_RULES);
+        let mut played = vec![];
+        while played.len() < moves {
+            let result = replay::simulate(DAILY_BOARD_SIZE, seed, &played).unwrap();
+            played.push(result.board.hint().unwrap());
+        }
+        let result = replay::simulate(DAILY_BOARD_SIZE, seed, &played).unwrap();
+        let game = GameFinished {
+            seed,
+            board_size: DAILY_BOARD_SIZE,
+            wrap: false,
+            rule: MergeRule::Classic,
+            score: result.score,
+            max_tile: result.max_tile,
+            moves: played,
+            daily: Some(date),
+            mode: GameMode::Classic,
+            millis: 0,
+        };
+        DailyResult::new(date, CLASSIC_RULES, &game)
+    }
+
+    #[test]
+    fn test_seeds_depend_on_date_and_rules() {
+        let tomorrow = Date::from_days(today().to_days() + 1);
+        assert_eq!(
+            daily_seed(today(), CLASSIC_RULES),
+            daily_seed(today(), CLASSIC_RULES)
+        );
+        assert_ne!(
+            daily_seed(today(), CLASSIC_RULES),
+            daily_seed(tomorrow, CLASSIC_RULES)
+        );
+        assert_ne!(
+            daily_seed(today(), CLASSIC_RULES),
+            daily_seed(today(), "fibonacci")
+        );
+    }
+
+    #[test]
+    fn test_result_strings_round_trip() {
+        let result = played(today(), 40);
+        let shared = result.to_string();
+        assert!(shared.starts_with("2048daily:2026-10-18:classic:"));
+        assert_eq!(shared.parse::<DailyResult>(), Ok(result.clone()));
+        assert_eq!(result.verify(), Ok(()));
+
+        assert!("2048daily:2026-10-18".parse::<DailyResult>().is_err());
+        let wrong_count = shared.replace(":40:", ":41:");
+        assert!(wrong_count.parse::<DailyResult>().is_err());
+    }
+
+    #[test]
+    fn test_made_up_results_do_not_verify() {
+        let result = played(today(), 40);
+        let better = DailyResult {
+            score: result.score + 4,
+            ..result.clone()
+        };
+        assert!(better.verify().is_err());
+        let other_day = DailyResult {
+            date: Date::from_days(today().to_days() - 1),
+            ..result.clone()
+        };
+        assert!(other_day.verify().is_err());
+    }
+
+    #[test]
+    fn test_one_attempt_per_day() {
+        let mut log = DailyLog::default();
+        assert!(log.record(played(today(), 5)));
+        assert!(!log.record(played(today(), 10)));
+        assert_eq!(log.get(today()).unwrap().moves.len(), 5);
+        let tomorrow = Date::from_days(today().to_days() + 1);
+        assert!(log.record(played(tomorrow, 10)));
+        assert_eq!(DailyLog::parse(&log.to_ron()), Ok(log));
+    }
+}
@@ -2,8 +2,9 @@ use std::fmt;
 use std::str::FromStr;
 
 use bevy::prelude::Component;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Component)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Component, Serialize, Deserialize)]
 pub enum Direction {
     Left,
     Right,
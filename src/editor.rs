@@ -0,0 +1,525 @@
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+use bevy::window::PrimaryWindow;
+
+use crate::board::Board;
+use crate::direction::Direction;
+use crate::menu::AppState;
+use crate::merge::MergeRule;
+use crate::settings::Settings;
+use crate::shape::Rounded;
+use crate::spawner::Spawn;
+use crate::theme::{ThemedText, Themes};
+use crate::tile::position::Position;
+use crate::tile::value::Value;
+use crate::{random_seed, spawn_tile, to_screen, GameState, NewGame, TILE_GAP, TILE_SIZE};
+
+const HELP: &str = "click or type 0-9 a b x to set a tile, arrows move\n\
+    delete clears, f fills, n picks the next spawn\n\
+    e exports, enter plays from here, esc leaves";
+
+/// The tile after `value` when clicking through the ladder of `rule`.
+/// Board notation ends at its top, a wall comes after it and then an empty
+/// cell again.
+pub fn next_value(value: Value, rule: MergeRule) -> Value {
+    let ladder = rule.ladder();
+    match (value, rule.rank(&value)) {
+        (Value::Empty, _) => Value::Number(ladder[0]),
+        (Value::Number(_), Some(rank)) if rank + 1 < ladder.len() => {
+            Value::Number(ladder[rank + 1])
+        }
+        (Value::Number(_), _) => Value::Wall,
+        (Value::Wall, _) => Value::Empty,
+    }
+}
+
+pub fn previous_value(value: Value, rule: MergeRule) -> Value {
+    let ladder = rule.ladder();
+    match (value, rule.rank(&value)) {
+        (Value::Empty, _) => Value::Wall,
+        (Value::Number(_), Some(rank)) if rank > 0 => Value::Number(ladder[rank - 1]),
+        (Value::Number(_), _) => Value::Empty,
+        (Value::Wall, _) => Value::Number(rule.max_tile()),
+    }
+}
+
+/// The cell under `point`, in the coordinates of the board entity.
+pub fn cell_at(point: Vec2, board_size: usize) -> Option<Position> {
+    let step = TILE_SIZE + TILE_GAP;
+    let col = (point.x / step).round();
+    let row = (-point.y / step).round();
+    let size = board_size as f32;
+    if !(0.0..size).contains(&col) || !(0.0..size).contains(&row) {
+        return None;
+    }
+    let center = Vec2::new(col * step, -row * step);
+    ((point - center).abs().max_element() <= TILE_SIZE / 2.0).then_some(Position {
+        row: row as usize,
+        col: col as usize,
+    })
+}
+
+/// A line of the `CASES` table in `board.rs` for moving `board` towards
+/// `direction`. The table plays classic rules without wrap around, other
+/// boards have no line.
+pub fn test_case_line(board: &Board, direction: Direction, name: &str) -> Option<String> {
+    if board.wrap || board.rule != MergeRule::Classic {
+        return None;
+    }
+    let mut moved_board = board.clone();
+    let n_events = board.plan_slide_and_merge(&direction).len();
+    let moved = moved_board.slide_and_merge(direction);
+    Some(format!(
+        "TestCase {{ in_board: \"{}\", board: \"{}\", n_events: {}, in_direction: {:<18}moved: {}, name: \"{}\" }},",
+        board,
+        moved_board,
+        n_events,
+        format!("Direction::{:?},", direction),
+        moved,
+        name
+    ))
+}
+
+/// What the editor works on besides the board of the game.
+#[derive(Resource, Debug)]
+pub struct Editor {
+    pub cursor: Position,
+    /// The tile that spawns after the first move, a random one when `None`.
+    pub next: Option<Spawn>,
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Editor {
+            cursor: Position { row: 0, col: 0 },
+            next: None,
+        }
+    }
+}
+
+impl Editor {
+    /// No chosen spawn, then the two values new tiles get at the cursor, a 2
+    /// and a 4 under classic rules.
+    fn cycle_next(&mut self) {
+        let Position { row, col } = self.cursor;
+        let value = match self.next {
+            Some(spawn) if spawn.row == row && spawn.col == col => match spawn.value {
+                '1' => Some('2'),
+                _ => None,
+            },
+            _ => Some('1'),
+        };
+        self.next = value.map(|value| Spawn { row, col, value });
+    }
+
+    fn describe_next(&self, rule: MergeRule) -> String {
+        match self
+            .next
+            .and_then(|spawn| Some((spawn, spawn.value(rule)?)))
+        {
+            Some((spawn, value)) => format!(
+                "next spawn: {} at row {}, column {}",
+                value.text_value(),
+                spawn.row + 1,
+                spawn.col + 1
+            ),
+            None => "next spawn: random".to_string(),
+        }
+    }
+}
+
+/// Help and the state of the editor, in the bottom left corner.
+#[derive(Component)]
+pub struct EditorHud;
+
+/// The frame around the cell that typing changes.
+#[derive(Component)]
+pub struct EditorCursor;
+
+/// Edits the running game's board, or an empty one, in a new game that
+/// nothing has been played in yet.
+fn start_editing(
+    mut commands: Commands,
+    themes: Res<Themes>,
+    settings: Res<Settings>,
+    game: Option<Res<GameState>>,
+    mut editor: ResMut<Editor>,
+    mut new_games: EventWriter<NewGame>,
+) {
+    let board = match game {
+        Some(game) => game.board.to_string(),
+        None => Board::new(settings.board_size).to_string(),
+    };
+    new_games.send(NewGame::position(random_seed(), board, vec![]));
+    *editor = Editor::default();
+
+    let hud = TextBundle::from_section(
+        "",
+        TextStyle {
+            font: themes.font.clone(),
+            font_size: 20.0,
+            color: themes.current().text,
+        },
+    )
+    .with_style(Style {
+        position_type: PositionType::Absolute,
+        bottom: Val::Px(12.0),
+        left: Val::Px(12.0),
+        ..Default::default()
+    });
+    commands.spawn(hud).insert(EditorHud).insert(ThemedText);
+}
+
+#[allow(clippy::type_complexity)]
+fn stop_editing(
+    mut commands: Commands,
+    leftovers: Query<Entity, Or<(With<EditorHud>, With<EditorCursor>)>>,
+) {
+    for entity in leftovers.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Puts `value` on `position` and replaces the tile entity there.
+fn set_cell(
+    commands: &mut Commands,
+    themes: &Themes,
+    game: &mut GameState,
+    position: Position,
+    value: Value,
+) {
+    if game.board.get_value(&position) == value {
+        return;
+    }
+    if let Some(entity) = game.entities.remove(&position) {
+        commands.entity(entity).despawn_recursive();
+    }
+    game.board.set_value(&position, value);
+    if value != Value::Empty {
+        let entity = spawn_tile(commands, themes, &position, &value, Vec3::ONE);
+        commands.entity(game.board_entity).add_child(entity);
+        game.entities.insert(position, entity);
+    }
+}
+
+/// The value the notation character `key` stands for under `rule`.
+fn typed_value(key: KeyCode, rule: MergeRule) -> Option<Value> {
+    let notation = match key {
+        KeyCode::Digit0 | KeyCode::Numpad0 => "0",
+        KeyCode::Digit1 | KeyCode::Numpad1 => "1",
+        KeyCode::Digit2 | KeyCode::Numpad2 => "2",
+        KeyCode::Digit3 | KeyCode::Numpad3 => "3",
+        KeyCode::Digit4 | KeyCode::Numpad4 => "4",
+        KeyCode::Digit5 | KeyCode::Numpad5 => "5",
+        KeyCode::Digit6 | KeyCode::Numpad6 => "6",
+        KeyCode::Digit7 | KeyCode::Numpad7 => "7",
+        KeyCode::Digit8 | KeyCode::Numpad8 => "8",
+        KeyCode::Digit9 | KeyCode::Numpad9 => "9",
+        KeyCode::KeyA => "A",
+        KeyCode::KeyB => "B",
+        KeyCode::KeyX => "X",
+        _ => return None,
+    };
+    rule.parse(notation)
+}
+
+/// Prints the board notation and a `CASES` line for every direction, as
+/// long as the table can hold them.
+fn export(board: &Board) {
+    println!("board: {}", board);
+    let lines: Option<Vec<String>> = [
+        Direction::Left,
+        Direction::Right,
+        Direction::Up,
+        Direction::Down,
+    ]
+    .into_iter()
+    .map(|direction| test_case_line(board, direction, "editor"))
+    .collect();
+    match lines {
+        Some(lines) => lines.iter().for_each(|line| println!("{}", line)),
+        None => println!(
+            "no CASES lines for {}{} boards, the table is classic without wrap around",
+            board.rule,
+            if board.wrap { " wrapping" } else { "" }
+        ),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn edit_board(
+    mut commands: Commands,
+    themes: Res<Themes>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    transforms: Query<&GlobalTransform>,
+    mut game: ResMut<GameState>,
+    mut editor: ResMut<Editor>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut new_games: EventWriter<NewGame>,
+) {
+    let game = game.as_mut();
+    let size = game.board.size;
+    let rule = game.board.rule;
+    let mut clicked = None;
+    if let (Ok(window), Ok((camera, camera_transform)), Ok(board_transform)) = (
+        windows.get_single(),
+        cameras.get_single(),
+        transforms.get(game.board_entity),
+    ) {
+        let to_board = board_transform.affine().inverse();
+        clicked = window
+            .cursor_position()
+            .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
+            .map(|point| to_board.transform_point3(point.extend(0.0)).truncate())
+            .and_then(|point| cell_at(point, size));
+    }
+    if let Some(position) = clicked {
+        if mouse.just_pressed(MouseButton::Left) {
+            editor.cursor = position;
+            let value = next_value(game.board.get_value(&position), rule);
+            set_cell(&mut commands, &themes, game, position, value);
+        } else if mouse.just_pressed(MouseButton::Right) {
+            editor.cursor = position;
+            let value = previous_value(game.board.get_value(&position), rule);
+            set_cell(&mut commands, &themes, game, position, value);
+        }
+    }
+
+    let cursor = &mut editor.cursor;
+    if keys.just_pressed(KeyCode::ArrowLeft) {
+        cursor.col = cursor.col.saturating_sub(1);
+    }
+    if keys.just_pressed(KeyCode::ArrowRight) {
+        cursor.col = (cursor.col + 1).min(size - 1);
+    }
+    if keys.just_pressed(KeyCode::ArrowUp) {
+        cursor.row = cursor.row.saturating_sub(1);
+    }
+    if keys.just_pressed(KeyCode::ArrowDown) {
+        cursor.row = (cursor.row + 1).min(size - 1);
+    }
+    let cursor = *cursor;
+    if let Some(value) = keys
+        .get_just_pressed()
+        .find_map(|key| typed_value(*key, rule))
+    {
+        set_cell(&mut commands, &themes, game, cursor, value);
+    }
+    if keys.any_just_pressed([KeyCode::Delete, KeyCode::Backspace]) {
+        let cells: Vec<Position> = game.board.tiles.keys().copied().collect();
+        for position in cells {
+            set_cell(&mut commands, &themes, game, position, Value::Empty);
+        }
+    }
+    if keys.just_pressed(KeyCode::KeyF) {
+        let value = match game.board.get_value(&cursor) {
+            Value::Empty => Value::Number(rule.ladder()[0]),
+            value => value,
+        };
+        let empty: Vec<Position> = game
+            .board
+            .tiles
+            .iter()
+            .filter(|(_, value)| **value == Value::Empty)
+            .map(|(position, _)| *position)
+            .collect();
+        for position in empty {
+            set_cell(&mut commands, &themes, game, position, value);
+        }
+    }
+    if keys.just_pressed(KeyCode::KeyN) {
+        editor.cycle_next();
+    }
+    if keys.just_pressed(KeyCode::KeyE) {
+        export(&game.board);
+    }
+    if keys.just_pressed(KeyCode::Enter) {
+        if game.board.max_tile() == 0 {
+            println!("place some tiles to play from first");
+            return;
+        }
+        let spawns = editor.next.into_iter().collect();
+        new_games.send(NewGame::position(
+            random_seed(),
+            game.board.to_string(),
+            spawns,
+        ));
+        next_state.set(AppState::Playing);
+    }
+}
+
+/// Keeps the cursor frame on the board of the game being edited.
+fn place_cursor(
+    mut commands: Commands,
+    themes: Res<Themes>,
+    game: Res<GameState>,
+    editor: Res<Editor>,
+    mut cursors: Query<(Entity, &Parent, &mut Transform), With<EditorCursor>>,
+) {
+    let translation = to_screen(&editor.cursor).extend(0.08);
+    let mut placed = false;
+    for (entity, parent, mut transform) in cursors.iter_mut() {
+        if parent.get() != game.board_entity {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        placed = true;
+        if transform.translation != translation {
+            transform.translation = translation;
+        }
+    }
+    if placed {
+        return;
+    }
+    let frame = SpriteBundle {
+        sprite: Sprite {
+            color: themes.current().text,
+            anchor: Anchor::Center,
+            custom_size: Some(Vec2::splat(TILE_SIZE + TILE_GAP * 0.6)),
+            ..Default::default()
+        },
+        transform: Transform::from_translation(translation),
+        ..Default::default()
+    };
+    let cursor = commands
+        .spawn(frame)
+        .insert(EditorCursor)
+        .insert(Rounded)
+        .id();
+    commands.entity(game.board_entity).add_child(cursor);
+}
+
+fn update_editor_hud(
+    game: Res<GameState>,
+    editor: Res<Editor>,
+    mut huds: Query<&mut Text, With<EditorHud>>,
+) {
+    let value = format!(
+        "{}\n{}\nboard: {}",
+        HELP,
+        editor.describe_next(game.board.rule),
+        game.board
+    );
+    for mut text in huds.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Editor>()
+            .add_systems(OnEnter(AppState::Editor), start_editing)
+            .add_systems(OnExit(AppState::Editor), stop_editing)
+            .add_systems(
+                Update,
+                (edit_board, place_cursor, update_editor_hud)
+                    .chain()
+                    .run_if(in_state(AppState::Editor).and_then(resource_exists::<GameState>)),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_values_cycle_through_the_notation() {
+        for rule in MergeRule::ALL {
+            let mut value = Value::Empty;
+            let mut seen = vec![];
+            loop {
+                value = next_value(value, rule);
+                if value == Value::Empty {
+                    break;
+                }
+                seen.push(rule.notation(&value));
+            }
+            assert_eq!(seen.concat(), "123456789ABX", "{}", rule);
+            for value in [
+                Value::Empty,
+                Value::Number(rule.ladder()[0]),
+                Value::Number(rule.max_tile()),
+                Value::Wall,
+            ] {
+                assert_eq!(previous_value(next_value(value, rule), rule), value);
+            }
+        }
+        assert_eq!(
+            typed_value(KeyCode::Digit4, MergeRule::Fibonacci),
+            Some(Value::Number(5))
+        );
+    }
+
+    #[test]
+    fn test_cells_under_points() {
+        let step = TILE_SIZE + TILE_GAP;
+        assert_eq!(cell_at(Vec2::ZERO, 4), Some(Position { row: 0, col: 0 }));
+        assert_eq!(
+            cell_at(Vec2::new(3.0 * step + 10.0, -step + 10.0), 4),
+            Some(Position { row: 1, col: 3 })
+        );
+        // the gap between cells and the outside of the board
+        assert_eq!(cell_at(Vec2::new(step / 2.0, 0.0), 4), None);
+        assert_eq!(cell_at(Vec2::new(4.0 * step, 0.0), 4), None);
+        assert_eq!(cell_at(Vec2::new(0.0, step), 4), None);
+    }
+
+    #[test]
+    fn test_exported_lines_match_the_cases_table() {
+        let board: Board = "0122000000000000".parse().unwrap();
+        assert_eq!(
+            test_case_line(&board, Direction::Left, "slide and merge").unwrap(),
+            r#"TestCase { in_board: "0122000000000000", board: "1300000000000000", n_events: 2, in_direction: Direction::Left,  moved: true, name: "slide and merge" },"#
+        );
+        let empty = Board::new(4);
+        assert_eq!(
+            test_case_line(&empty, Direction::Right, "no move empty").unwrap(),
+            r#"TestCase { in_board: "0000000000000000", board: "0000000000000000", n_events: 0, in_direction: Direction::Right, moved: false, name: "no move empty" },"#
+        );
+        // the table has neither wrap around nor other merge rules
+        assert_eq!(
+            test_case_line(&board.clone().wrapping(true), Direction::Left, "wrap"),
+            None
+        );
+        for rule in MergeRule::ALL.into_iter().skip(1) {
+            let board = Board::parse_with("0122000000000000", rule).unwrap();
+            assert_eq!(test_case_line(&board, Direction::Left, "rule"), None);
+        }
+    }
+
+    #[test]
+    fn test_next_spawn_cycles_at_the_cursor() {
+        let mut editor = Editor::default();
+        assert_eq!(
+            editor.describe_next(MergeRule::Classic),
+            "next spawn: random"
+        );
+        editor.cycle_next();
+        assert_eq!(
+            editor.describe_next(MergeRule::Classic),
+            "next spawn: 2 at row 1, column 1"
+        );
+        editor.cycle_next();
+        assert_eq!(
+            editor.describe_next(MergeRule::Classic),
+            "next spawn: 4 at row 1, column 1"
+        );
+        editor.cycle_next();
+        assert_eq!(editor.next, None);
+        editor.cycle_next();
+        editor.cursor = Position { row: 2, col: 3 };
+        editor.cycle_next();
+        assert_eq!(
+            editor.describe_next(MergeRule::Classic),
+            "next spawn: 2 at row 3, column 4"
+        );
+    }
+}
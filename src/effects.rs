@@ -0,0 +1,301 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::accessibility::{Accessibility, Motion};
+use crate::action::Action;
+use crate::settings::Settings;
+use crate::shape::Rounded;
+use crate::theme::Themes;
+use crate::tile::value::Value;
+use crate::{to_screen, GameState};
+
+/// Merges into this value or more burst into particles.
+pub const PARTICLE_THRESHOLD: u32 = 128;
+/// Merges into these values also shake the screen.
+pub const MILESTONES: [u32; 3] = [512, 1024, 2048];
+
+const PARTICLE_LIFETIME: f32 = 0.6;
+/// Initial speed in board units per second.
+const PARTICLE_SPEED: f32 = 300.0;
+/// How much speed particles keep per second.
+const PARTICLE_DRAG: f32 = 0.05;
+const PARTICLE_SIZE: f32 = 12.0;
+const SHAKE_DURATION: f32 = 0.35;
+/// Largest camera offset in pixels.
+const SHAKE_STRENGTH: f32 = 10.0;
+
+/// How much effects there are. Reduced motion turns them off regardless.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum EffectQuality {
+    Off,
+    Low,
+    #[default]
+    High,
+}
+
+impl EffectQuality {
+    pub fn next(&self) -> EffectQuality {
+        match self {
+            EffectQuality::Off => EffectQuality::Low,
+            EffectQuality::Low => EffectQuality::High,
+            EffectQuality::High => EffectQuality::Off,
+        }
+    }
+
+    /// The quality that is actually played with `motion`.
+    pub fn with_motion(&self, motion: Motion) -> EffectQuality {
+        match motion {
+            Motion::Full => *self,
+            Motion::Reduced | Motion::Off => EffectQuality::Off,
+        }
+    }
+
+    /// Particles in the burst of a merge into `value`. Milestones burst twice
+    /// as much.
+    pub fn particle_count(&self, value: u32) -> usize {
+        if value < PARTICLE_THRESHOLD {
+            return 0;
+        }
+        let count = match self {
+            EffectQuality::Off => 0,
+            EffectQuality::Low => 6,
+            EffectQuality::High => 16,
+        };
+        if MILESTONES.contains(&value) {
+            count * 2
+        } else {
+            count
+        }
+    }
+
+    /// How far the screen shakes for a merge into `value`, in pixels.
+    pub fn shake_strength(&self, value: u32) -> f32 {
+        if !MILESTONES.contains(&value) {
+            return 0.0;
+        }
+        match self {
+            EffectQuality::Off => 0.0,
+            EffectQuality::Low => SHAKE_STRENGTH / 2.0,
+            EffectQuality::High => SHAKE_STRENGTH,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Particle {
+    velocity: Vec2,
+    timer: Timer,
+}
+
+/// The running screen shake, if any.
+#[derive(Resource, Debug)]
+pub struct Shake {
+    strength: f32,
+    timer: Timer,
+}
+
+impl Default for Shake {
+    fn default() -> Self {
+        let mut timer = Timer::from_seconds(SHAKE_DURATION, TimerMode::Once);
+        timer.tick(timer.duration());
+        Shake {
+            strength: 0.0,
+            timer,
+        }
+    }
+}
+
+impl Shake {
+    fn start(&mut self, strength: f32) {
+        // a second milestone during a shake never makes it weaker
+        if self.timer.finished() || strength > self.strength {
+            self.strength = strength;
+        }
+        self.timer.reset();
+    }
+}
+
+/// Camera offset of a shake of `strength` at progress `t`, pointing towards
+/// `angle`. The shake dies down quadratically.
+pub fn shake_offset(strength: f32, t: f32, angle: f32) -> Vec2 {
+    let decay = (1.0 - t.clamp(0.0, 1.0)).powi(2);
+    Vec2::from_angle(angle) * strength * decay
+}
+
+fn spawn_effects(
+    mut commands: Commands,
+    themes: Res<Themes>,
+    quality: Res<EffectQuality>,
+    accessibility: Res<Accessibility>,
+    game: Res<GameState>,
+    mut shake: ResMut<Shake>,
+    mut actions: EventReader<Action>,
+) {
+    let quality = quality.with_motion(accessibility.motion);
+    for action in actions.read() {
+        let Some((to, value)) = action.merged() else {
+            continue;
+        };
+        // bursts grow with the rung, whatever the rule's values are
+        let Value::Number(n) = game.board.rule.classic(&value) else {
+            continue;
+        };
+        let count = quality.particle_count(n);
+        let color = themes.tile_color(&value);
+        let origin = to_screen(&to);
+        for i in 0..count {
+            let angle = TAU * (i as f32 + rand::random::<f32>()) / count as f32;
+            let speed = PARTICLE_SPEED * (0.5 + rand::random::<f32>());
+            let particle = SpriteBundle {
+                sprite: Sprite {
+                    color,
+                    custom_size: Some(Vec2::splat(PARTICLE_SIZE)),
+                    ..Default::default()
+                },
+                transform: Transform::from_translation(origin.extend(0.5)),
+                ..Default::default()
+            };
+            let particle_id = commands
+                .spawn(particle)
+                .insert(Rounded)
+                .insert(Particle {
+                    velocity: Vec2::from_angle(angle) * speed,
+                    timer: Timer::from_seconds(PARTICLE_LIFETIME, TimerMode::Once),
+                })
+                .id();
+            commands.entity(game.board_entity).add_child(particle_id);
+        }
+        let strength = quality.shake_strength(n);
+        if strength > 0.0 {
+            shake.start(strength);
+        }
+    }
+}
+
+/// Particles fly outwards, slow down, shrink and fade.
+fn update_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut particles: Query<(Entity, &mut Particle, &mut Transform, &mut Sprite)>,
+) {
+    let dt = time.delta_seconds();
+    for (entity, mut particle, mut transform, mut sprite) in particles.iter_mut() {
+        particle.timer.tick(time.delta());
+        if particle.timer.finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        particle.velocity *= PARTICLE_DRAG.powf(dt);
+        transform.translation += (particle.velocity * dt).extend(0.0);
+        let t = particle.timer.fraction();
+        transform.scale = Vec3::splat(1.0 - t * 0.5);
+        sprite.color.set_a(1.0 - t);
+    }
+}
+
+fn update_shake(
+    time: Res<Time>,
+    mut shake: ResMut<Shake>,
+    mut cameras: Query<&mut Transform, With<Camera2d>>,
+) {
+    if shake.timer.finished() {
+        return;
+    }
+    shake.timer.tick(time.delta());
+    let offset = if shake.timer.finished() {
+        Vec2::ZERO
+    } else {
+        let angle = rand::random::<f32>() * TAU;
+        shake_offset(shake.strength, shake.timer.fraction(), angle)
+    };
+    for mut transform in cameras.iter_mut() {
+        transform.translation = offset.extend(transform.translation.z);
+    }
+}
+
+fn load_effect_settings(settings: Res<Settings>, mut quality: ResMut<EffectQuality>) {
+    *quality = settings.effects;
+}
+
+fn store_effect_settings(quality: Res<EffectQuality>, mut settings: ResMut<Settings>) {
+    if settings.effects != *quality {
+        settings.effects = *quality;
+    }
+}
+
+pub struct EffectsPlugin;
+
+impl Plugin for EffectsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EffectQuality>()
+            .init_resource::<Shake>()
+            .add_systems(PreStartup, load_effect_settings)
+            .add_systems(
+                Update,
+                (
+                    spawn_effects.run_if(resource_exists::<GameState>),
+                    update_particles,
+                    update_shake,
+                ),
+            )
+            .add_systems(
+                Last,
+                store_effect_settings.run_if(resource_changed::<EffectQuality>),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_particles_only_for_big_merges() {
+        let high = EffectQuality::High;
+        assert_eq!(high.particle_count(64), 0);
+        assert!(high.particle_count(128) > 0);
+        assert_eq!(high.particle_count(1024), high.particle_count(256) * 2);
+        assert!(EffectQuality::Low.particle_count(256) < high.particle_count(256));
+        assert_eq!(EffectQuality::Off.particle_count(2048), 0);
+    }
+
+    #[test]
+    fn test_shake_only_on_milestones() {
+        let high = EffectQuality::High;
+        assert_eq!(high.shake_strength(256), 0.0);
+        assert_eq!(high.shake_strength(4096), 0.0);
+        for milestone in MILESTONES {
+            assert!(high.shake_strength(milestone) > 0.0);
+        }
+        assert!(EffectQuality::Low.shake_strength(512) < high.shake_strength(512));
+        assert_eq!(EffectQuality::Off.shake_strength(2048), 0.0);
+    }
+
+    #[test]
+    fn test_reduced_motion_turns_effects_off() {
+        let high = EffectQuality::High;
+        assert_eq!(high.with_motion(Motion::Full), high);
+        assert_eq!(high.with_motion(Motion::Reduced), EffectQuality::Off);
+        assert_eq!(high.with_motion(Motion::Off), EffectQuality::Off);
+    }
+
+    #[test]
+    fn test_shake_dies_down() {
+        let start = shake_offset(10.0, 0.0, 0.0);
+        assert!((start - Vec2::new(10.0, 0.0)).length() < 1e-4);
+        assert!(shake_offset(10.0, 0.5, 0.0).length() < start.length());
+        assert_eq!(shake_offset(10.0, 1.0, 1.0), Vec2::ZERO);
+    }
+
+    #[test]
+    fn test_stronger_shake_wins() {
+        let mut shake = Shake::default();
+        shake.start(5.0);
+        shake.start(2.0);
+        assert_eq!(shake.strength, 5.0);
+        shake.start(10.0);
+        assert_eq!(shake.strength, 10.0);
+    }
+}
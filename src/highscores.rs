@@ -0,0 +1,485 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::board::Board;
+use crate::date::Date;
+use crate::merge::MergeRule;
+use crate::mode::GameMode;
+use crate::replay;
+use crate::settings::{settings_path, write_atomically, Settings};
+use crate::GameFinished;
+
+/// Lives next to the settings file.
+pub const HIGHSCORE_FILE: &str = "highscores.ron";
+/// Entries kept per board size and rule set.
+pub const TABLE_SIZE: usize = 10;
+/// The rules of classic games, other modes are named by `GameMode`'s
+/// `Display`.
+pub const CLASSIC_RULES: &str = "classic";
+/// Marks the rules of games on wrapping boards, e.g. `classic+wrap`.
+pub const WRAP_RULES: &str = "+wrap";
+
+/// The rule set name of the table for `mode` on a wrapping board or not,
+/// with merge `rule`. Merge rules other than classic are named after the
+/// mode, e.g. `classic+fibonacci+wrap`.
+pub fn rules(mode: GameMode, wrap: bool, rule: MergeRule) -> String {
+    let mut rules = mode.to_string();
+    if rule != MergeRule::Classic {
+        rules.push_str(&format!("+{}", rule));
+    }
+    if wrap {
+        rules.push_str(WRAP_RULES);
+    }
+    rules
+}
+
+/// One game on the high score table. `replay` holds its moves as letters,
+/// see `replay::encode`, and `millis` the time it was played for.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    pub name: String,
+    pub score: u32,
+    pub max_tile: u32,
+    pub moves: usize,
+    #[serde(default)]
+    pub millis: u64,
+    pub date: Date,
+    pub seed: u64,
+    pub board_size: usize,
+    pub rules: String,
+    pub replay: String,
+    pub checksum: u64,
+}
+
+/// What re-verifying an entry found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verification {
+    Valid,
+    /// The checksum does not match, the entry was edited.
+    ChecksumMismatch,
+    /// The replay does not play or does not end in the entry's numbers.
+    ReplayMismatch,
+}
+
+/// FNV-1a, which is plenty to notice edits by hand.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+impl Entry {
+    pub(crate) fn new(name: &str, date: Date, game: &GameFinished) -> Entry {
+        let mut entry = Entry {
+            name: name.to_string(),
+            score: game.score,
+            max_tile: game.max_tile,
+            moves: game.moves.len(),
+            millis: game.millis,
+            date,
+            seed: game.seed,
+            board_size: game.board_size,
+            rules: rules(game.mode, game.wrap, game.rule),
+            replay: replay::encode(&game.moves),
+            checksum: 0,
+        };
+        entry.checksum = entry.expected_checksum();
+        entry
+    }
+
+    /// Checksum over every other field.
+    pub fn expected_checksum(&self) -> u64 {
+        let mut fields = format!(
+            "{}|{}|{}|{}|{}|{}|{}|{}|{}",
+            self.name,
+            self.score,
+            self.max_tile,
+            self.moves,
+            self.date,
+            self.seed,
+            self.board_size,
+            self.rules,
+            self.replay
+        );
+        // entries from before timed modes have no time
+        if self.millis > 0 {
+            fields.push_str(&format!("|{}", self.millis));
+        }
+        fnv1a(fields.as_bytes())
+    }
+
+    /// The mode of the table the entry is on, unknown rules rank like
+    /// classic games.
+    pub fn mode(&self) -> GameMode {
+        let mode = self.rules.split('+').next().unwrap_or_default();
+        mode.parse().unwrap_or_default()
+    }
+
+    /// Whether the game was played on a wrapping board.
+    pub fn wraps(&self) -> bool {
+        self.rules.ends_with(WRAP_RULES)
+    }
+
+    /// Which tiles merged in the game.
+    pub fn merge_rule(&self) -> MergeRule {
+        self.rules
+            .split('+')
+            .skip(1)
+            .find_map(|part| part.parse().ok())
+            .unwrap_or_default()
+    }
+
+    /// Checks the checksum and plays the replay to see that it ends in the
+    /// recorded score, largest tile and move count.
+    pub fn verify(&self) -> Verification {
+        if self.checksum != self.expected_checksum() {
+            return Verification::ChecksumMismatch;
+        }
+        let within_limit = match self.mode() {
+            GameMode::MoveLimit { moves } => self.moves <= moves as usize,
+            _ => true,
+        };
+        let result = replay::decode(&self.replay)
+            .filter(|moves| moves.len() == self.moves && within_limit)
+            .and_then(|moves| {
+                let board = Board::new(self.board_size)
+                    .wrapping(self.wraps())
+                    .merging(self.merge_rule());
+                replay::simulate_on(board, self.seed, &moves)
+            });
+        match result {
+            Some(result) if result.score == self.score && result.max_tile == self.max_tile => {
+                Verification::Valid
+            }
+            _ => Verification::ReplayMismatch,
+        }
+    }
+}
+
+/// The best games of every board size and rule set, best first as their
+/// mode ranks them. Entries are verified when they are loaded or added.
+#[derive(Resource, Debug, Clone, Default, PartialEq)]
+pub struct HighScores {
+    entries: Vec<Entry>,
+    verified: Vec<Verification>,
+}
+
+impl HighScores {
+    pub fn new(entries: Vec<Entry>) -> HighScores {
+        let mut highscores = HighScores::default();
+        for entry in entries {
+            highscores.insert(entry);
+        }
+        highscores
+    }
+
+    pub fn parse(ron: &str) -> Result<HighScores, String> {
+        ron::from_str(ron)
+            .map(HighScores::new)
+            .map_err(|err| err.to_string())
+    }
+
+    pub fn to_ron(&self) -> String {
+        ron::ser::to_string_pretty(&self.entries, ron::ser::PrettyConfig::default())
+            .expect("high scores serialize")
+    }
+
+    /// Loads `path`, starting with an empty table when there is no file yet
+    /// or it cannot be read.
+    pub fn load_or_default(path: &Path) -> HighScores {
+        match fs::read_to_string(path) {
+            Ok(ron) => HighScores::parse(&ron).unwrap_or_else(|err| {
+                warn!("malformed high scores: {} ({})", err, path.display());
+                HighScores::default()
+            }),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => HighScores::default(),
+            Err(err) => {
+                warn!("could not read high scores: {} ({})", err, path.display());
+                HighScores::default()
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        write_atomically(path, &self.to_ron())
+    }
+
+    pub fn get(&self, index: usize) -> Option<(&Entry, Verification)> {
+        Some((self.entries.get(index)?, self.verified[index]))
+    }
+
+    /// Indices of the entries for `board_size` and `rules`, best first.
+    pub fn table(&self, board_size: usize, rules: &str) -> Vec<usize> {
+        (0..self.entries.len())
+            .filter(|index| {
+                let entry = &self.entries[*index];
+                entry.board_size == board_size && entry.rules == rules
+            })
+            .collect()
+    }
+
+    /// Adds `entry` behind the entries that rank the same or better and
+    /// returns its rank, or `None` when it did not make the table. The worst
+    /// entry drops out of a full table.
+    pub fn insert(&mut self, entry: Entry) -> Option<usize> {
+        let mode = entry.mode();
+        let table = self.table(entry.board_size, &entry.rules);
+        let rank = table
+            .iter()
+            .position(|index| mode.compare(&entry, &self.entries[*index]).is_lt())
+            .unwrap_or(table.len());
+        if rank >= TABLE_SIZE {
+            return None;
+        }
+        // the entry goes in front of the one it beats, which never comes
+        // after the one dropping out
+        let index = table.get(rank).copied().unwrap_or(self.entries.len());
+        if table.len() == TABLE_SIZE {
+            let last = table[TABLE_SIZE - 1];
+            self.entries.remove(last);
+            self.verified.remove(last);
+        }
+        self.verified.insert(index, entry.verify());
+        self.entries.insert(index, entry);
+        Some(rank)
+    }
+}
+
+/// Where the high score file lives, `None` when settings are not persisted.
+pub fn highscores_path() -> Option<PathBuf> {
+    settings_path().map(|path| path.with_file_name(HIGHSCORE_FILE))
+}
+
+#[derive(Resource, Debug)]
+pub struct HighScorePath(pub Option<PathBuf>);
+
+/// Puts finished games on the table and saves it when one made it.
+fn record_highscores(
+    path: Res<HighScorePath>,
+    settings: Res<Settings>,
+    mut highscores: ResMut<HighScores>,
+    mut finished: EventReader<GameFinished>,
+) {
+    let mut changed = false;
+    for game in finished.read() {
+        if !game.mode.qualifies(game) {
+            continue;
+        }
+        let entry = Entry::new(&settings.player_name, Date::today(), game);
+        if let Some(rank) = highscores.insert(entry) {
+            info!("new high score {} at rank {}", game.score, rank + 1);
+            changed = true;
+        }
+    }
+    if !changed {
+        return;
+    }
+    if let Some(path) = &path.0 {
+        if let Err(err) = highscores.save(path) {
+            warn!("could not write high scores: {} ({})", err, path.display());
+        }
+    }
+}
+
+pub struct HighScorePlugin;
+
+impl Plugin for HighScorePlugin {
+    fn build(&self, app: &mut App) {
+        let path = highscores_path();
+        let highscores = path
+            .as_deref()
+            .map(HighScores::load_or_default)
+            .unwrap_or_default();
+        app.insert_resource(highscores)
+            .insert_resource(HighScorePath(path))
+            .add_systems(Last, record_highscores);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::direction::Direction;
+
+    /// A real game of `moves` moves on a `board_size` board.
+    fn game(board_size: usize, seed: u64, moves: usize) -> GameFinished {
+        game_on(Board::new(board_size), seed, moves)
+    }
+
+    fn game_on(board: Board, seed: u64, moves: usize) -> GameFinished {
+        let (board_size, wrap, rule) = (board.size, board.wrap, board.rule);
+        let mut played = vec![];
+        while played.len() < moves {
+            let result = replay::simulate_on(board.clone(), seed, &played).unwrap();
+            played.push(result.board.hint().unwrap());
+        }
+        let result = replay::simulate_on(board, seed, &played).unwrap();
+        GameFinished {
+            seed,
+            board_size,
+            wrap,
+            rule,
+            score: result.score,
+            max_tile: result.max_tile,
+            moves: played,
+            daily: None,
+            mode: GameMode::Classic,
+            millis: 0,
+        }
+    }
+
+    fn entry(board_size: usize, score: u32) -> Entry {
+        let game = GameFinished {
+            seed: 1,
+            board_size,
+            wrap: false,
+            rule: MergeRule::Classic,
+            score,
+            max_tile: 4,
+            moves: vec![Direction::Left],
+            daily: None,
+            mode: GameMode::Classic,
+            millis: 0,
+        };
+        Entry::new("ana", Date::from_days(0), &game)
+    }
+
+    fn scores(highscores: &HighScores, board_size: usize) -> Vec<u32> {
+        highscores
+            .table(board_size, CLASSIC_RULES)
+            .into_iter()
+            .map(|index| highscores.get(index).unwrap().0.score)
+            .collect()
+    }
+
+    #[test]
+    fn test_real_games_verify() {
+        let entry = Entry::new("ana", Date::today(), &game(4, 3, 30));
+        assert_eq!(entry.moves, 30);
+        assert_eq!(entry.verify(), Verification::Valid);
+    }
+
+    #[test]
+    fn test_edits_are_detected() {
+        let valid = Entry::new("ana", Date::today(), &game(4, 3, 30));
+        let edited = Entry {
+            score: valid.score * 2,
+            ..valid.clone()
+        };
+        assert_eq!(edited.verify(), Verification::ChecksumMismatch);
+
+        // a consistent checksum does not help a made up score
+        let mut forged = edited.clone();
+        forged.checksum = forged.expected_checksum();
+        assert_eq!(forged.verify(), Verification::ReplayMismatch);
+
+        let mut truncated = valid.clone();
+        truncated.replay.pop();
+        truncated.checksum = truncated.expected_checksum();
+        assert_eq!(truncated.verify(), Verification::ReplayMismatch);
+    }
+
+    #[test]
+    fn test_tables_keep_the_best() {
+        let mut highscores = HighScores::default();
+        for score in [40, 10, 30] {
+            highscores.insert(entry(4, score));
+        }
+        highscores.insert(entry(5, 20));
+        assert_eq!(scores(&highscores, 4), vec![40, 30, 10]);
+        assert_eq!(scores(&highscores, 5), vec![20]);
+        // ties go behind earlier entries
+        assert_eq!(highscores.insert(entry(4, 30)), Some(2));
+
+        for score in 100..TABLE_SIZE as u32 + 100 {
+            highscores.insert(entry(4, score));
+        }
+        assert_eq!(highscores.table(4, CLASSIC_RULES).len(), TABLE_SIZE);
+        assert_eq!(highscores.insert(entry(4, 1)), None);
+        assert_eq!(highscores.insert(entry(4, 1000)), Some(0));
+        assert!(!scores(&highscores, 4).contains(&100));
+        assert_eq!(scores(&highscores, 5), vec![20]);
+    }
+
+    #[test]
+    fn test_modes_have_their_own_tables() {
+        let race = GameMode::TargetRace { target: 4 };
+        let mut highscores = HighScores::default();
+        highscores.insert(entry(4, 50));
+        for (score, millis) in [(10, 9000), (30, 5000), (20, 7000)] {
+            let mut entry = Entry {
+                millis,
+                rules: race.to_string(),
+                ..entry(4, score)
+            };
+            entry.checksum = entry.expected_checksum();
+            highscores.insert(entry);
+        }
+        assert_eq!(scores(&highscores, 4), vec![50]);
+        // races rank the fastest first, whatever the score
+        let race_scores: Vec<u32> = highscores
+            .table(4, &race.to_string())
+            .into_iter()
+            .map(|index| highscores.get(index).unwrap().0.score)
+            .collect();
+        assert_eq!(race_scores, vec![30, 20, 10]);
+    }
+
+    #[test]
+    fn test_wrapping_games_have_their_own_table() {
+        let wrapped = Entry::new(
+            "ana",
+            Date::from_days(0),
+            &game_on(Board::new(4).wrapping(true), 3, 12),
+        );
+        assert_eq!(wrapped.rules, "classic+wrap");
+        assert_eq!(wrapped.mode(), GameMode::Classic);
+        assert!(wrapped.wraps());
+        // replayed on a wrapping board
+        assert_eq!(wrapped.verify(), Verification::Valid);
+        let highscores = HighScores::new(vec![wrapped, entry(4, 10)]);
+        assert_eq!(scores(&highscores, 4), vec![10]);
+        assert_eq!(
+            highscores
+                .table(4, &rules(GameMode::Classic, true, MergeRule::Classic))
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_merge_rules_have_their_own_table() {
+        let board = Board::new(4).merging(MergeRule::Fibonacci);
+        let fibonacci = Entry::new("ana", Date::from_days(0), &game_on(board, 3, 12));
+        assert_eq!(fibonacci.rules, "classic+fibonacci");
+        assert_eq!(fibonacci.mode(), GameMode::Classic);
+        assert_eq!(fibonacci.merge_rule(), MergeRule::Fibonacci);
+        assert!(!fibonacci.wraps());
+        // replayed with fibonacci merges
+        assert_eq!(fibonacci.verify(), Verification::Valid);
+        assert_eq!(
+            rules(GameMode::MoveLimit { moves: 100 }, true, MergeRule::Triples),
+            "move-limit:100+triples+wrap"
+        );
+        let highscores = HighScores::new(vec![fibonacci, entry(4, 10)]);
+        assert_eq!(scores(&highscores, 4), vec![10]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let highscores = HighScores::new(vec![
+            Entry::new("ana", Date::today(), &game(5, 9, 10)),
+            entry(4, 10),
+        ]);
+        let loaded = HighScores::parse(&highscores.to_ron()).unwrap();
+        assert_eq!(loaded, highscores);
+        let played = loaded.table(5, CLASSIC_RULES)[0];
+        assert_eq!(loaded.get(played).unwrap().1, Verification::Valid);
+        let made_up = loaded.table(4, CLASSIC_RULES)[0];
+        assert_eq!(loaded.get(made_up).unwrap().1, Verification::ReplayMismatch);
+    }
+}
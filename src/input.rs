@@ -0,0 +1,518 @@
+use bevy::input::gamepad::{Gamepad, GamepadAxis, GamepadAxisType, GamepadButton};
+use bevy::input::touch::Touches;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy::window::PrimaryWindow;
+use serde::{Deserialize, Serialize};
+
+use crate::direction::Direction;
+use crate::menu::AppState;
+use crate::settings::Settings;
+use crate::theme::BoardBackground;
+
+/// How far the stick has to be pushed before it counts as a move.
+pub const STICK_THRESHOLD: f32 = 0.5;
+
+/// Swipes shorter than this, in pixels, are ignored.
+pub const MIN_SWIPE_DISTANCE: f32 = 40.0;
+/// Swipes slower than this, in pixels per second, are ignored.
+pub const MIN_SWIPE_VELOCITY: f32 = 150.0;
+
+/// Turns raw input into `GameAction` events, gameplay systems run after it.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct InputSet;
+
+/// What the player wants to do, independent of the device that asked for it.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GameAction {
+    Move(Direction),
+    Undo,
+    Restart,
+    Hint,
+    ReplayStep,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Gamepad(GamepadButtonType),
+    /// The left stick pushed past `STICK_THRESHOLD` towards a direction.
+    LeftStick(Direction),
+}
+
+/// Maps inputs to actions. Several inputs may trigger the same action.
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bindings(pub Vec<(Binding, GameAction)>);
+
+impl Default for Bindings {
+    fn default() -> Self {
+        use Binding::{Gamepad as Pad, Key, LeftStick};
+        use Direction::{Down, Left, Right, Up};
+        use GameAction::Move;
+        let moves = [
+            (Left, KeyCode::ArrowLeft, KeyCode::KeyA, KeyCode::KeyH),
+            (Right, KeyCode::ArrowRight, KeyCode::KeyD, KeyCode::KeyL),
+            (Up, KeyCode::ArrowUp, KeyCode::KeyW, KeyCode::KeyK),
+            (Down, KeyCode::ArrowDown, KeyCode::KeyS, KeyCode::KeyJ),
+        ];
+        let mut bindings = vec![];
+        for (direction, arrow, wasd, vim) in moves {
+            let dpad = match direction {
+                Left => GamepadButtonType::DPadLeft,
+                Right => GamepadButtonType::DPadRight,
+                Up => GamepadButtonType::DPadUp,
+                Down => GamepadButtonType::DPadDown,
+            };
+            bindings.extend([
+                (Key(arrow), Move(direction)),
+                (Key(wasd), Move(direction)),
+                (Key(vim), Move(direction)),
+                (Pad(dpad), Move(direction)),
+                (LeftStick(direction), Move(direction)),
+            ]);
+        }
+        bindings.extend([
+            (Key(KeyCode::KeyU), GameAction::Undo),
+            (Key(KeyCode::Backspace), GameAction::Undo),
+            (Pad(GamepadButtonType::West), GameAction::Undo),
+            (Key(KeyCode::KeyR), GameAction::Restart),
+            (Pad(GamepadButtonType::Select), GameAction::Restart),
+            (Key(KeyCode::Tab), GameAction::Hint),
+            (Pad(GamepadButtonType::North), GameAction::Hint),
+            (Key(KeyCode::Space), GameAction::ReplayStep),
+            (Pad(GamepadButtonType::South), GameAction::ReplayStep),
+        ]);
+        Bindings(bindings)
+    }
+}
+
+impl Bindings {
+    /// Actions whose input was pressed this frame, in binding order and
+    /// without duplicates. `stick` holds the directions the left stick of any
+    /// gamepad was pushed into this frame.
+    pub fn triggered(
+        &self,
+        keys: &ButtonInput<KeyCode>,
+        pads: &ButtonInput<GamepadButton>,
+        stick: &[Direction],
+    ) -> Vec<GameAction> {
+        let mut actions = vec![];
+        for (binding, action) in self.0.iter() {
+            let pressed = match binding {
+                Binding::Key(key) => keys.just_pressed(*key),
+                Binding::Gamepad(button) => pads
+                    .get_just_pressed()
+                    .any(|pressed| pressed.button_type == *button),
+                Binding::LeftStick(direction) => stick.contains(direction),
+            };
+            if pressed && !actions.contains(action) {
+                actions.push(*action);
+            }
+        }
+        actions
+    }
+
+    pub fn inputs_for(&self, action: GameAction) -> impl Iterator<Item = &Binding> {
+        self.0
+            .iter()
+            .filter(move |(_, bound)| *bound == action)
+            .map(|(binding, _)| binding)
+    }
+}
+
+/// The direction along the dominant axis of `v`, with y pointing up.
+fn dominant_direction(v: Vec2) -> Direction {
+    if v.x.abs() > v.y.abs() {
+        if v.x > 0.0 {
+            Direction::Right
+        } else {
+            Direction::Left
+        }
+    } else if v.y > 0.0 {
+        Direction::Up
+    } else {
+        Direction::Down
+    }
+}
+
+/// The direction the stick points to, if it is pushed far enough. The
+/// stronger axis wins.
+pub fn stick_direction(x: f32, y: f32) -> Option<Direction> {
+    if x.abs().max(y.abs()) < STICK_THRESHOLD {
+        None
+    } else {
+        Some(dominant_direction(Vec2::new(x, y)))
+    }
+}
+
+/// How far and how fast a drag has to be to count as a swipe.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SwipeConfig {
+    /// In pixels.
+    pub min_distance: f32,
+    /// In pixels per second, measured from press to release.
+    pub min_velocity: f32,
+}
+
+impl Default for SwipeConfig {
+    fn default() -> Self {
+        SwipeConfig {
+            min_distance: MIN_SWIPE_DISTANCE,
+            min_velocity: MIN_SWIPE_VELOCITY,
+        }
+    }
+}
+
+impl SwipeConfig {
+    /// The direction of a drag by `delta` world units that took `seconds`,
+    /// if it is long and fast enough.
+    pub fn swipe_direction(&self, delta: Vec2, seconds: f32) -> Option<Direction> {
+        let distance = delta.length();
+        if distance < self.min_distance || distance < self.min_velocity * seconds {
+            None
+        } else {
+            Some(dominant_direction(delta))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Pointer {
+    Mouse,
+    Touch(u64),
+}
+
+/// Drags in progress, by the pointer that started them. Only drags that
+/// start on the board are tracked.
+#[derive(Debug, Default)]
+struct Swipes(HashMap<Pointer, (Vec2, f32)>);
+
+impl Swipes {
+    fn press(&mut self, pointer: Pointer, position: Vec2, time: f32) {
+        self.0.insert(pointer, (position, time));
+    }
+
+    fn release(
+        &mut self,
+        pointer: Pointer,
+        position: Vec2,
+        time: f32,
+        config: &SwipeConfig,
+    ) -> Option<Direction> {
+        let (start, started) = self.0.remove(&pointer)?;
+        config.swipe_direction(position - start, time - started)
+    }
+}
+
+/// Only the moment the stick enters a direction counts, holding it does not
+/// repeat the move.
+fn stick_moves(
+    gamepads: impl IntoIterator<Item = Gamepad>,
+    axes: &Axis<GamepadAxis>,
+    held: &mut HashMap<Gamepad, Direction>,
+) -> Vec<Direction> {
+    let mut moves = vec![];
+    for gamepad in gamepads {
+        let axis = |axis_type| {
+            axes.get(GamepadAxis::new(gamepad, axis_type))
+                .unwrap_or_default()
+        };
+        let x = axis(GamepadAxisType::LeftStickX);
+        let y = axis(GamepadAxisType::LeftStickY);
+        match stick_direction(x, y) {
+            Some(direction) => {
+                if held.insert(gamepad, direction) != Some(direction) {
+                    moves.push(direction);
+                }
+            }
+            None => {
+                held.remove(&gamepad);
+            }
+        }
+    }
+    moves
+}
+
+fn read_input(
+    bindings: Res<Bindings>,
+    keys: Res<ButtonInput<KeyCode>>,
+    pads: Res<ButtonInput<GamepadButton>>,
+    gamepads: Res<Gamepads>,
+    axes: Res<Axis<GamepadAxis>>,
+    mut held: Local<HashMap<Gamepad, Direction>>,
+    mut actions: EventWriter<GameAction>,
+) {
+    let stick = stick_moves(gamepads.iter(), &axes, &mut held);
+    actions.send_batch(bindings.triggered(&keys, &pads, &stick));
+}
+
+/// Mouse drags and touch swipes that start on the board become moves.
+#[allow(clippy::too_many_arguments)]
+fn read_swipes(
+    config: Res<SwipeConfig>,
+    time: Res<Time>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    touches: Res<Touches>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    boards: Query<(&Sprite, &GlobalTransform), With<BoardBackground>>,
+    mut swipes: Local<Swipes>,
+    mut actions: EventWriter<GameAction>,
+) {
+    let (Ok(window), Ok((camera, camera_transform))) = (windows.get_single(), cameras.get_single())
+    else {
+        return;
+    };
+    let now = time.elapsed_seconds();
+    let to_world = |position| camera.viewport_to_world_2d(camera_transform, position);
+    let on_board = |position: Vec2| {
+        boards.iter().any(|(sprite, transform)| {
+            let size = sprite.custom_size.unwrap_or_default()
+                * transform.compute_transform().scale.truncate();
+            Rect::from_center_size(transform.translation().truncate(), size).contains(position)
+        })
+    };
+
+    let mut pressed = vec![];
+    let mut released = vec![];
+    if let Some(cursor) = window.cursor_position().and_then(to_world) {
+        if mouse.just_pressed(MouseButton::Left) {
+            pressed.push((Pointer::Mouse, cursor));
+        }
+        if mouse.just_released(MouseButton::Left) {
+            released.push((Pointer::Mouse, cursor));
+        }
+    }
+    for touch in touches.iter_just_pressed() {
+        if let Some(position) = to_world(touch.position()) {
+            pressed.push((Pointer::Touch(touch.id()), position));
+        }
+    }
+    for touch in touches.iter_just_released() {
+        if let Some(position) = to_world(touch.position()) {
+            released.push((Pointer::Touch(touch.id()), position));
+        }
+    }
+    for touch in touches.iter_just_canceled() {
+        swipes.0.remove(&Pointer::Touch(touch.id()));
+    }
+
+    for (pointer, position) in pressed {
+        if on_board(position) {
+            swipes.press(pointer, position, now);
+        }
+    }
+    for (pointer, position) in released {
+        if let Some(direction) = swipes.release(pointer, position, now, &config) {
+            actions.send(GameAction::Move(direction));
+        }
+    }
+}
+
+fn load_input_settings(
+    settings: Res<Settings>,
+    mut bindings: ResMut<Bindings>,
+    mut swipe: ResMut<SwipeConfig>,
+) {
+    *bindings = settings.key_bindings.clone();
+    *swipe = settings.swipe;
+}
+
+/// Bindings and swipe thresholds are stored with the other settings, so edits
+/// made in the settings file show up here and changes made in game end up in
+/// the file.
+fn store_input_settings(
+    bindings: Res<Bindings>,
+    swipe: Res<SwipeConfig>,
+    mut settings: ResMut<Settings>,
+) {
+    if settings.key_bindings != *bindings {
+        settings.key_bindings = bindings.clone();
+    }
+    if settings.swipe != *swipe {
+        settings.swipe = *swipe;
+    }
+}
+
+pub struct InputPlugin;
+
+impl Plugin for InputPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Bindings>()
+            .init_resource::<SwipeConfig>()
+            .add_event::<GameAction>()
+            .add_systems(PreStartup, load_input_settings)
+            .add_systems(
+                PreUpdate,
+                (read_input, read_swipes)
+                    .in_set(InputSet)
+                    .after(bevy::input::InputSystem)
+                    .run_if(in_state(AppState::Playing)),
+            )
+            .add_systems(
+                Last,
+                store_input_settings
+                    .run_if(resource_changed::<Bindings>.or_else(resource_changed::<SwipeConfig>)),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triggered(keys: &[KeyCode], buttons: &[GamepadButtonType]) -> Vec<GameAction> {
+        let mut key_input = ButtonInput::<KeyCode>::default();
+        for key in keys {
+            key_input.press(*key);
+        }
+        let mut pad_input = ButtonInput::<GamepadButton>::default();
+        for button in buttons {
+            pad_input.press(GamepadButton::new(Gamepad::new(0), *button));
+        }
+        Bindings::default().triggered(&key_input, &pad_input, &[])
+    }
+
+    #[test]
+    fn test_default_move_keys() {
+        let left = vec![GameAction::Move(Direction::Left)];
+        assert_eq!(triggered(&[KeyCode::ArrowLeft], &[]), left);
+        assert_eq!(triggered(&[KeyCode::KeyA], &[]), left);
+        assert_eq!(triggered(&[KeyCode::KeyH], &[]), left);
+        assert_eq!(triggered(&[], &[GamepadButtonType::DPadLeft]), left);
+        assert_eq!(
+            triggered(&[KeyCode::KeyK], &[]),
+            vec![GameAction::Move(Direction::Up)]
+        );
+    }
+
+    #[test]
+    fn test_other_actions() {
+        assert_eq!(triggered(&[KeyCode::KeyU], &[]), vec![GameAction::Undo]);
+        assert_eq!(triggered(&[KeyCode::KeyR], &[]), vec![GameAction::Restart]);
+        assert_eq!(triggered(&[KeyCode::Tab], &[]), vec![GameAction::Hint]);
+        assert_eq!(
+            triggered(&[], &[GamepadButtonType::South]),
+            vec![GameAction::ReplayStep]
+        );
+        assert_eq!(triggered(&[KeyCode::KeyQ], &[]), vec![]);
+    }
+
+    #[test]
+    fn test_same_action_from_two_inputs_fires_once() {
+        assert_eq!(
+            triggered(&[KeyCode::ArrowDown, KeyCode::KeyS], &[]),
+            vec![GameAction::Move(Direction::Down)]
+        );
+    }
+
+    #[test]
+    fn test_held_keys_do_not_repeat() {
+        let mut keys = ButtonInput::<KeyCode>::default();
+        let pads = ButtonInput::<GamepadButton>::default();
+        keys.press(KeyCode::KeyW);
+        let bindings = Bindings::default();
+        assert_eq!(bindings.triggered(&keys, &pads, &[]).len(), 1);
+        keys.clear();
+        assert_eq!(bindings.triggered(&keys, &pads, &[]), vec![]);
+    }
+
+    #[test]
+    fn test_rebinding() {
+        let mut bindings = Bindings(vec![]);
+        bindings
+            .0
+            .push((Binding::Key(KeyCode::KeyQ), GameAction::Restart));
+        let mut keys = ButtonInput::<KeyCode>::default();
+        keys.press(KeyCode::KeyQ);
+        keys.press(KeyCode::KeyR);
+        let pads = ButtonInput::<GamepadButton>::default();
+        assert_eq!(
+            bindings.triggered(&keys, &pads, &[]),
+            vec![GameAction::Restart]
+        );
+        assert_eq!(bindings.inputs_for(GameAction::Restart).count(), 1);
+    }
+
+    #[test]
+    fn test_stick() {
+        assert_eq!(stick_direction(0.2, -0.3), None);
+        assert_eq!(stick_direction(0.9, 0.4), Some(Direction::Right));
+        assert_eq!(stick_direction(-0.1, -0.8), Some(Direction::Down));
+        let stick = [Direction::Up];
+        let actions =
+            Bindings::default().triggered(&ButtonInput::default(), &ButtonInput::default(), &stick);
+        assert_eq!(actions, vec![GameAction::Move(Direction::Up)]);
+    }
+
+    #[test]
+    fn test_stick_moves_once_per_push() {
+        let gamepad = Gamepad::new(0);
+        let mut axes = Axis::<GamepadAxis>::default();
+        let mut held = HashMap::new();
+        let x = GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX);
+
+        axes.set(x, 1.0);
+        assert_eq!(
+            stick_moves([gamepad], &axes, &mut held),
+            vec![Direction::Right]
+        );
+        assert_eq!(stick_moves([gamepad], &axes, &mut held), vec![]);
+        axes.set(x, 0.0);
+        assert_eq!(stick_moves([gamepad], &axes, &mut held), vec![]);
+        axes.set(x, -1.0);
+        assert_eq!(
+            stick_moves([gamepad], &axes, &mut held),
+            vec![Direction::Left]
+        );
+    }
+
+    #[test]
+    fn test_swipe_direction() {
+        let config = SwipeConfig::default();
+        assert_eq!(
+            config.swipe_direction(Vec2::new(100.0, 30.0), 0.1),
+            Some(Direction::Right)
+        );
+        assert_eq!(
+            config.swipe_direction(Vec2::new(-50.0, -80.0), 0.1),
+            Some(Direction::Down)
+        );
+        // too short
+        assert_eq!(config.swipe_direction(Vec2::new(20.0, 0.0), 0.01), None);
+        // too slow
+        assert_eq!(config.swipe_direction(Vec2::new(0.0, 100.0), 2.0), None);
+    }
+
+    #[test]
+    fn test_swipes_track_pointers_separately() {
+        let config = SwipeConfig::default();
+        let mut swipes = Swipes::default();
+        swipes.press(Pointer::Touch(1), Vec2::ZERO, 0.0);
+        swipes.press(Pointer::Mouse, Vec2::ZERO, 0.0);
+        assert_eq!(
+            swipes.release(Pointer::Touch(1), Vec2::new(0.0, 90.0), 0.2, &config),
+            Some(Direction::Up)
+        );
+        assert_eq!(
+            swipes.release(Pointer::Touch(1), Vec2::new(0.0, 90.0), 0.2, &config),
+            None
+        );
+        assert_eq!(
+            swipes.release(Pointer::Mouse, Vec2::new(-90.0, 0.0), 0.2, &config),
+            Some(Direction::Left)
+        );
+        // releases without a press on the board are ignored
+        assert_eq!(
+            swipes.release(Pointer::Touch(2), Vec2::new(90.0, 0.0), 0.2, &config),
+            None
+        );
+    }
+
+    #[test]
+    fn test_bindings_round_trip() {
+        let bindings = Bindings::default();
+        let ron = ron::to_string(&bindings).unwrap();
+        assert_eq!(ron::from_str::<Bindings>(&ron).unwrap(), bindings);
+    }
+}
@@ -0,0 +1,229 @@
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+use crate::score::ScoreText;
+use crate::settings::Settings;
+use crate::{GameState, TILE_GAP, TILE_SIZE};
+
+/// Space kept free around everything, relative to the shorter window side.
+const MARGIN: f32 = 0.04;
+/// Height of headline and score above the board, relative to the board.
+const HEADER_HEIGHT: f32 = 0.3;
+/// Width of headline and score next to the board, relative to the board.
+const HEADER_WIDTH: f32 = 0.5;
+const HEADLINE_FONT: f32 = 0.16;
+const SCORE_FONT: f32 = 0.06;
+
+/// The big "2048" above or next to the board.
+#[derive(Component)]
+pub struct Headline;
+
+/// Where things go in the window. Tiles are built in board units of
+/// `TILE_SIZE` and `TILE_GAP`, the board entity is scaled by `scale` so the
+/// board fits the window. Text outside of the board is sized directly.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct Layout {
+    pub scale: f32,
+    pub tile_size: f32,
+    pub gap: f32,
+    /// Center of the board frame.
+    pub board_center: Vec2,
+    /// Top center of the headline.
+    pub headline: Vec2,
+    pub headline_font: f32,
+    /// Top center of the score.
+    pub score: Vec2,
+    pub score_font: f32,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout::compute(Vec2::new(1280.0, 720.0), 4)
+    }
+}
+
+/// Side length of a `board_size` board in board units, frame included.
+pub fn board_extent(board_size: usize) -> f32 {
+    (TILE_SIZE + TILE_GAP) * board_size as f32 + TILE_GAP
+}
+
+impl Layout {
+    /// Lays out a `board_size` board for a window of `window` logical
+    /// pixels. The header goes above the board or to its left, whichever
+    /// leaves the board more room, so portrait windows stack and wide
+    /// windows put things side by side.
+    pub fn compute(window: Vec2, board_size: usize) -> Layout {
+        let margin = MARGIN * window.min_element();
+        let free = (window - Vec2::splat(2.0 * margin)).max(Vec2::ONE);
+        let stacked = free.x.min(free.y / (1.0 + HEADER_HEIGHT));
+        let side_by_side = (free.x / (1.0 + HEADER_WIDTH)).min(free.y);
+        let side = stacked.max(side_by_side);
+        let headline_font = HEADLINE_FONT * side;
+        let score_font = SCORE_FONT * side;
+        let header = headline_font * 1.1 + score_font;
+
+        let (board_center, headline) = if stacked >= side_by_side {
+            let height = side * (1.0 + HEADER_HEIGHT);
+            (
+                Vec2::new(0.0, side / 2.0 - height / 2.0),
+                Vec2::new(0.0, height / 2.0),
+            )
+        } else {
+            let width = side * (1.0 + HEADER_WIDTH);
+            (
+                Vec2::new(width / 2.0 - side / 2.0, 0.0),
+                Vec2::new(-width / 2.0 + side * HEADER_WIDTH / 2.0, header / 2.0),
+            )
+        };
+
+        let scale = side / board_extent(board_size);
+        Layout {
+            scale,
+            tile_size: TILE_SIZE * scale,
+            gap: TILE_GAP * scale,
+            board_center,
+            headline,
+            headline_font,
+            score: headline - Vec2::new(0.0, headline_font * 1.1),
+            score_font,
+        }
+    }
+
+    /// Transform of the board entity. Cells sit at `to_screen` positions
+    /// inside it, starting with the top left one at the origin.
+    pub fn board_transform(&self, board_size: usize) -> Transform {
+        let offset = (TILE_SIZE + TILE_GAP) * (board_size as f32 - 1.0) * 0.5;
+        Transform {
+            translation: (self.board_center + Vec2::new(-offset, offset) * self.scale).extend(0.0),
+            scale: Vec3::new(self.scale, self.scale, 1.0),
+            ..Default::default()
+        }
+    }
+}
+
+/// Recomputes the layout when the window or the board size changes and moves
+/// the board, headline and score into place. A new board is placed as soon
+/// as it appears.
+#[allow(clippy::type_complexity)]
+fn update_layout(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    settings: Res<Settings>,
+    game: Option<Res<GameState>>,
+    mut layout: ResMut<Layout>,
+    mut placed_board: Local<Option<Entity>>,
+    mut items: ParamSet<(
+        Query<&mut Transform>,
+        Query<(&mut Transform, &mut Text), With<Headline>>,
+        Query<(&mut Transform, &mut Text), With<ScoreText>>,
+    )>,
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let board_size = game
+        .as_ref()
+        .map_or(settings.board_size, |game| game.board.size);
+    let computed = Layout::compute(Vec2::new(window.width(), window.height()), board_size);
+    let board_entity = game.as_ref().map(|game| game.board_entity);
+    if computed == *layout && *placed_board == board_entity && !layout.is_added() {
+        return;
+    }
+    *layout = computed;
+
+    if let Some(board_entity) = board_entity {
+        if let Ok(mut transform) = items.p0().get_mut(board_entity) {
+            *transform = layout.board_transform(board_size);
+            *placed_board = Some(board_entity);
+        }
+    }
+    for (mut transform, mut text) in items.p1().iter_mut() {
+        transform.translation = layout.headline.extend(transform.translation.z);
+        for section in text.sections.iter_mut() {
+            section.style.font_size = layout.headline_font;
+        }
+    }
+    for (mut transform, mut text) in items.p2().iter_mut() {
+        transform.translation = layout.score.extend(transform.translation.z);
+        for section in text.sections.iter_mut() {
+            section.style.font_size = layout.score_font;
+        }
+    }
+}
+
+pub struct LayoutPlugin;
+
+impl Plugin for LayoutPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Layout>().add_systems(
+            PostUpdate,
+            update_layout.before(bevy::transform::TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board_rect(layout: &Layout, board_size: usize) -> Rect {
+        Rect::from_center_size(
+            layout.board_center,
+            Vec2::splat(board_extent(board_size) * layout.scale),
+        )
+    }
+
+    #[test]
+    fn test_board_fits_the_window() {
+        for window in [
+            Vec2::new(1280.0, 720.0),
+            Vec2::new(720.0, 1280.0),
+            Vec2::new(500.0, 500.0),
+            Vec2::new(3840.0, 2160.0),
+        ] {
+            for board_size in 3..=6 {
+                let layout = Layout::compute(window, board_size);
+                let board = board_rect(&layout, board_size);
+                let screen = Rect::from_center_size(Vec2::ZERO, window);
+                assert!(screen.contains(board.min) && screen.contains(board.max));
+                assert!(screen.contains(layout.headline));
+                assert!((layout.gap / layout.tile_size - TILE_GAP / TILE_SIZE).abs() < 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn test_portrait_stacks_and_landscape_goes_side_by_side() {
+        let portrait = Layout::compute(Vec2::new(600.0, 1000.0), 4);
+        let board = board_rect(&portrait, 4);
+        assert_eq!(portrait.headline.x, 0.0);
+        // the score ends above the board
+        assert!(portrait.score.y - portrait.score_font >= board.max.y);
+
+        let landscape = Layout::compute(Vec2::new(1600.0, 800.0), 4);
+        let board = board_rect(&landscape, 4);
+        assert!(landscape.headline.x < board.min.x);
+        assert!(landscape.headline.y < board.max.y);
+    }
+
+    #[test]
+    fn test_tiles_shrink_on_larger_boards_and_grow_with_the_window() {
+        let window = Vec2::new(800.0, 800.0);
+        let small = Layout::compute(window, 3);
+        let large = Layout::compute(window, 6);
+        assert!(large.tile_size < small.tile_size);
+        assert_eq!(small.board_center, large.board_center);
+        assert_eq!(small.headline_font, large.headline_font);
+
+        let bigger = Layout::compute(window * 2.0, 3);
+        assert!((bigger.tile_size - small.tile_size * 2.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_board_transform_centers_the_frame() {
+        let layout = Layout::compute(Vec2::new(700.0, 900.0), 5);
+        let transform = layout.board_transform(5);
+        let offset = (TILE_SIZE + TILE_GAP) * 2.0;
+        let frame_center = transform.transform_point(Vec3::new(offset, -offset, 0.0));
+        assert!((frame_center.truncate() - layout.board_center).length() < 0.01);
+    }
+}
@@ -1,10 +1,13 @@
 use core::prelude::v1;
-use rand::SeedableRng;
-use rand_chacha::ChaCha8Rng;
-use std::{mem::swap, str::FromStr};
+use std::{collections::VecDeque, mem::swap, str::FromStr};
 
+use accessibility::AccessibilityPlugin;
 use action::Action;
+use animation::{
+    finish_animation, update_animations, Animating, AnimatingTile, Animation, AnimationConfig,
+};
 use bevy::{
+    app::AppExit,
     asset::io::memory::Dir,
     core_pipeline::core_2d::Transparent2d,
     prelude::*,
@@ -16,47 +19,66 @@ use bevy::{
     utils::HashMap,
 };
 use board::Board;
+use consistency::ActionLog;
+use daily::{daily_seed, DailyPlugin, DailyResult, DAILY_BOARD_SIZE};
+use date::Date;
 use direction::Direction;
-use tile::value::{Value, EMPTY_TILE_BG_COLOR};
+use editor::EditorPlugin;
+use effects::EffectsPlugin;
+use highscores::{HighScorePlugin, CLASSIC_RULES};
+use input::{GameAction, InputPlugin, InputSet};
+use layout::{Headline, LayoutPlugin};
+use menu::{AppState, MenuPlugin};
+use merge::MergeRule;
+use mode::{GameMode, ModeEnd, ModePlugin};
+use puzzle::{PuzzlePlugin, Puzzles};
+use replay::{Replay, ReplayPlugin};
+use score::ScorePlugin;
+use settings::{Settings, SettingsPlugin};
+use shape::{Rounded, Shadow, ShapePlugin, SHADOW_ALPHA, SHADOW_OFFSET};
+use sound::SoundPlugin;
+use spawner::{Spawn, Spawner};
+use stats::StatsPlugin;
+use theme::{BoardBackground, EmptyCell, ThemePlugin, ThemeSet, ThemedText, Themes};
+use tile::value::Value;
 use tile::{position::Position, Tile};
 
+mod accessibility;
 mod action;
+mod animation;
 mod board;
+mod consistency;
+mod daily;
+mod date;
 mod direction;
+mod editor;
+mod effects;
+mod highscores;
+mod input;
+mod layout;
+mod menu;
+mod merge;
+mod mode;
+mod puzzle;
+#[cfg(test)]
+mod reference;
+mod replay;
+mod score;
+mod settings;
+mod shape;
+mod sound;
+mod spawner;
+mod stats;
+mod theme;
 mod tile;
 
 const TILE_SIZE: f32 = 100.0;
 const TILE_GAP: f32 = 20.0;
-const BG_COLOR: Color = Color::rgb(1.0, 1.0, 1.0);
-const FONT_PATH: &str = "Arial.ttf";
-
-#[derive(Component, Debug, Deref, DerefMut)]
-struct Animating {
-    #[deref]
-    timer: Timer,
-    animation: Animation,
-}
-
-#[derive(Debug, Clone)]
-enum Animation {
-    Sliding {
-        entity: Entity,
-        tile: Tile,
-        to: Position,
-    },
-    Merging {
-        entity1: Entity,
-        entity2: Entity,
-        tile1: Tile,
-        tile2: Tile,
-        to: Tile,
-    },
-    Spawning {
-        entity: Entity,
-        tile: Tile,
-    },
-}
+const MAX_QUEUED_MOVES: usize = 4;
+const MAX_UNDO_STEPS: usize = 32;
 
+/// 0 is fully opaque, 1 is invisible. Set on tiles, which pass it on to
+/// their children.
 #[derive(Component, Clone, Debug)]
 struct Transparency(f32);
 
@@ -80,6 +102,177 @@ struct TileBundle {
     spatial: SpatialBundle,
 }
 
+/// Sent once when the game is decided, either by creating the largest tile,
+/// by running out of moves or by the end its mode sets.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+enum GameOutcome {
+    Won,
+    Lost,
+    Ended(ModeEnd),
+}
+
+/// Throws away the current game and starts a new one, the daily challenge
+/// or a replay of an earlier game.
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+struct NewGame {
+    seed: u64,
+    replay: Option<Replay>,
+    daily: Option<Date>,
+    /// The index of a puzzle in `Puzzles`, which brings its own board.
+    puzzle: Option<usize>,
+    /// A position to start from instead of an empty board, in board
+    /// notation, and the tiles that spawn first on it.
+    board: Option<String>,
+    spawns: Vec<Spawn>,
+}
+
+impl NewGame {
+    fn with_seed(seed: u64) -> Self {
+        NewGame {
+            seed,
+            replay: None,
+            daily: None,
+            puzzle: None,
+            board: None,
+            spawns: vec![],
+        }
+    }
+
+    /// Watches `moves` being played on a `board_size` board seeded with `seed`.
+    fn watch(
+        seed: u64,
+        board_size: usize,
+        wrap: bool,
+        rule: MergeRule,
+        moves: Vec<Direction>,
+    ) -> Self {
+        NewGame {
+            seed,
+            replay: Some(Replay {
+                board_size,
+                wrap,
+                rule,
+                moves,
+            }),
+            daily: None,
+            puzzle: None,
+            board: None,
+            spawns: vec![],
+        }
+    }
+
+    /// The daily challenge of `date`.
+    fn daily(date: Date) -> Self {
+        NewGame {
+            seed: daily_seed(date, CLASSIC_RULES),
+            replay: None,
+            daily: Some(date),
+            puzzle: None,
+            board: None,
+            spawns: vec![],
+        }
+    }
+
+    /// The puzzle with `index` in `Puzzles`.
+    fn puzzle(index: usize) -> Self {
+        NewGame {
+            seed: 0,
+            replay: None,
+            daily: None,
+            puzzle: Some(index),
+            board: None,
+            spawns: vec![],
+        }
+    }
+
+    /// Plays on from `board`, with `spawns` before the random tiles.
+    fn position(seed: u64, board: String, spawns: Vec<Spawn>) -> Self {
+        NewGame {
+            seed,
+            replay: None,
+            daily: None,
+            puzzle: None,
+            board: Some(board),
+            spawns,
+        }
+    }
+
+    fn board_size(&self, default: usize) -> usize {
+        match (&self.replay, self.daily) {
+            (Some(replay), _) => replay.board_size,
+            (None, Some(_)) => DAILY_BOARD_SIZE,
+            (None, None) => default,
+        }
+    }
+
+    /// Whether the board wraps around, replays as they were played and
+    /// daily challenges never.
+    fn wrap(&self, default: bool) -> bool {
+        match (&self.replay, self.daily) {
+            (Some(replay), _) => replay.wrap,
+            (None, Some(_)) => false,
+            (None, None) => default,
+        }
+    }
+
+    /// Which tiles merge, like `wrap`.
+    fn rule(&self, default: MergeRule) -> MergeRule {
+        match (&self.replay, self.daily) {
+            (Some(replay), _) => replay.rule,
+            (None, Some(_)) => MergeRule::Classic,
+            (None, None) => default,
+        }
+    }
+
+    /// Replays and daily challenges are played without a mode, positions
+    /// in the sandbox.
+    fn mode(&self, default: GameMode) -> GameMode {
+        if self.board.is_some() {
+            GameMode::Sandbox
+        } else if self.replay.is_some() || self.daily.is_some() {
+            GameMode::Classic
+        } else {
+            default
+        }
+    }
+}
+
+/// A move of the player that changed the board. Replays do not send it.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+struct Turn(Direction);
+
+/// A `Turn` the player took back.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+struct Undone(Direction);
+
+/// Sent once for every game the player has made moves in, when it is lost,
+/// replaced by a new game or the app quits. `seed`, `board_size` and
+/// `moves` replay it.
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+struct GameFinished {
+    seed: u64,
+    board_size: usize,
+    wrap: bool,
+    rule: MergeRule,
+    score: u32,
+    max_tile: u32,
+    moves: Vec<Direction>,
+    /// The day of a daily challenge.
+    daily: Option<Date>,
+    mode: GameMode,
+    /// Time on the clock of timed modes.
+    millis: u64,
+}
+
+/// The game as it was before a move, for undo. The spawner goes back too,
+/// so seed and moves keep reproducing the game.
+#[derive(Debug, Clone)]
+struct Snapshot {
+    board: Board,
+    score: u32,
+    spawner: Spawner,
+}
+
 #[derive(Resource)]
 struct GameState {
     board: Board,
@@ -87,7 +280,28 @@ struct GameState {
     board_entity: Entity,
     deferred_events: Vec<Action>,
     replay: Vec<Direction>,
-    rng: ChaCha8Rng,
+    /// Set while watching a replay, the player's moves are ignored then.
+    watching: bool,
+    /// The day of a daily challenge, which cannot be undone.
+    daily: Option<Date>,
+    /// The index of the puzzle being played.
+    puzzle: Option<usize>,
+    queued_moves: VecDeque<Direction>,
+    history: VecDeque<Snapshot>,
+    /// Every move that changed the board, undone moves are dropped.
+    moves: Vec<Direction>,
+    score: u32,
+    won: bool,
+    /// Set once `GameFinished` was sent.
+    finished: bool,
+    mode: GameMode,
+    /// Seconds on the clock, which only runs in timed modes.
+    seconds: f32,
+    /// Set when the mode ended the game.
+    ended: Option<ModeEnd>,
+    spawner: Spawner,
+    seed: u64,
+    log: ActionLog,
 }
 
 impl GameState {
@@ -101,6 +315,177 @@ impl GameState {
             panic!("no entity found at position {:?}", from);
         }
     }
+    fn remember(&mut self) {
+        if self.history.len() == MAX_UNDO_STEPS {
+            self.history.pop_front();
+        }
+        self.history.push_back(Snapshot {
+            board: self.board.clone(),
+            score: self.score,
+            spawner: self.spawner.clone(),
+        });
+    }
+    /// Marks the game finished and describes it, unless it is a replay, has
+    /// no moves or was finished before.
+    fn finish(&mut self) -> Option<GameFinished> {
+        if self.watching || self.finished || self.moves.is_empty() {
+            return None;
+        }
+        self.finished = true;
+        Some(GameFinished {
+            seed: self.seed,
+            board_size: self.board.size,
+            wrap: self.board.wrap,
+            rule: self.board.rule,
+            score: self.score,
+            max_tile: self.board.max_tile(),
+            moves: self.moves.clone(),
+            daily: self.daily,
+            mode: self.mode,
+            millis: (self.seconds * 1000.0) as u64,
+        })
+    }
+    /// Spawns an entity for every tile on the board.
+    fn spawn_tiles(&mut self, commands: &mut Commands, themes: &Themes) {
+        for (position, value) in self.board.tiles.iter() {
+            if *value == Value::Empty {
+                continue;
+            }
+            let entity = spawn_tile(commands, themes, position, value, Vec3::ONE);
+            commands.entity(self.board_entity).add_child(entity);
+            self.entities.insert(*position, entity);
+        }
+    }
+    fn queue_move(&mut self, direction: Direction) {
+        if self.queued_moves.len() < MAX_QUEUED_MOVES {
+            self.queued_moves.push_back(direction);
+        }
+    }
+    /// Applies `action` to the board and to the entity lookup right away and
+    /// starts its animation. Keeping the lookup in sync here, instead of when
+    /// an animation finishes, lets animations finish in any order. `direction`
+    /// is the move the action is part of, tiles that go over the edge of a
+    /// wrapping board are animated along it.
+    fn play_action(
+        &mut self,
+        commands: &mut Commands,
+        themes: &Themes,
+        config: &AnimationConfig,
+        action: &Action,
+        direction: Option<Direction>,
+        animate: bool,
+    ) {
+        let wrap = |from: &Position, to: &Position| match direction {
+            Some(direction) if self.board.crosses_edge(from, to, &direction) => {
+                wrap_offset(direction, self.board.size)
+            }
+            _ => Vec2::ZERO,
+        };
+        match action {
+            Action::SlideTile(tile, to) => {
+                let entity = *self.get_entity(&tile.position).unwrap();
+                commands.entity(entity).insert(Animating::new(
+                    config,
+                    Animation::Sliding {
+                        entity,
+                        tile: *tile,
+                        to: *to,
+                        wrap: wrap(&tile.position, to),
+                    },
+                ));
+                self.move_entity(&tile.position, to);
+            }
+            Action::MergeTiles(tile1, tile2, to, value) => {
+                let e1 = self.entities.remove(&tile1.position).unwrap();
+                let e2 = self.entities.remove(&tile2.position).unwrap();
+                let animation = Animation::Merging {
+                    entity1: e1,
+                    entity2: e2,
+                    tile1: *tile1,
+                    tile2: *tile2,
+                    to: Tile {
+                        position: *to,
+                        value: *value,
+                    },
+                    wrap1: wrap(&tile1.position, to),
+                    wrap2: wrap(&tile2.position, to),
+                };
+                commands
+                    .entity(e1)
+                    .insert(Animating::new(config, animation.clone()));
+                commands
+                    .entity(e2)
+                    .insert(Animating::new(config, animation));
+                self.entities.insert(*to, e1);
+            }
+            Action::MergeThreeTiles(tile1, tile2, tile3, to, value) => {
+                let e1 = self.entities.remove(&tile1.position).unwrap();
+                let to_tile = Tile {
+                    position: *to,
+                    value: *value,
+                };
+                commands.entity(e1).insert(Animating::new(
+                    config,
+                    Animation::Merging {
+                        entity1: e1,
+                        entity2: e1,
+                        tile1: *tile1,
+                        tile2: *tile1,
+                        to: to_tile,
+                        wrap1: wrap(&tile1.position, to),
+                        wrap2: wrap(&tile1.position, to),
+                    },
+                ));
+                // the other two fade out underneath the first one
+                for tile in [tile2, tile3] {
+                    let entity = self.entities.remove(&tile.position).unwrap();
+                    commands.entity(entity).insert(Animating::new(
+                        config,
+                        Animation::Merging {
+                            entity1: e1,
+                            entity2: entity,
+                            tile1: *tile1,
+                            tile2: *tile,
+                            to: to_tile,
+                            wrap1: wrap(&tile1.position, to),
+                            wrap2: wrap(&tile.position, to),
+                        },
+                    ));
+                }
+                self.entities.insert(*to, e1);
+            }
+            Action::SpawnRandomTile(tile) => {
+                let scale = if animate { Vec3::ZERO } else { Vec3::ONE };
+                let entity = spawn_tile(commands, themes, &tile.position, &tile.value, scale);
+                if animate {
+                    commands.entity(entity).insert(Animating::new(
+                        config,
+                        Animation::Spawning {
+                            entity,
+                            tile: *tile,
+                        },
+                    ));
+                }
+                commands.entity(self.board_entity).add_child(entity);
+                self.entities.insert(tile.position, entity);
+            }
+        }
+        self.board.apply(action.clone());
+        self.score += action.score();
+        self.log.record(action);
+    }
+}
+
+/// How far past its cell a tile that goes over the edge in `direction`
+/// travels, once across a board of `size` cells.
+fn wrap_offset(direction: Direction, size: usize) -> Vec2 {
+    let span = size as f32 * (TILE_SIZE + TILE_GAP);
+    match direction {
+        Direction::Left => Vec2::new(-span, 0.0),
+        Direction::Right => Vec2::new(span, 0.0),
+        Direction::Up => Vec2::new(0.0, span),
+        Direction::Down => Vec2::new(0.0, -span),
+    }
 }
 
 fn to_screen(pos: &Position) -> Vec2 {
@@ -112,19 +497,19 @@ fn to_screen(pos: &Position) -> Vec2 {
 
 fn spawn_tile(
     commands: &mut Commands,
-    font: &Handle<Font>,
+    themes: &Themes,
     pos: &Position,
     value: &Value,
+    scale: Vec3,
 ) -> Entity {
     let text = Text2dBundle {
         text: Text::from_section(
             value.text_value(),
             // pos.to_string(),
             TextStyle {
-                font: font.clone(),
+                font: themes.font.clone(),
                 font_size: 40.0,
-                // color: value.text_color(),
-                color: Color::BLACK,
+                color: themes.tile_text_color(value),
             },
         ),
         text_anchor: Anchor::Center,
@@ -142,12 +527,9 @@ fn spawn_tile(
 
     let square = SpriteBundle {
         sprite: Sprite {
-            color: value.tile_color(),
+            color: themes.tile_color(value),
             anchor: Anchor::Center,
-            rect: Some(Rect {
-                min: Vec2::new(0.0, 0.0),
-                max: Vec2::new(TILE_SIZE, TILE_SIZE),
-            }),
+            custom_size: Some(Vec2::splat(TILE_SIZE)),
             ..Default::default()
         },
         transform: Transform {
@@ -160,8 +542,13 @@ fn spawn_tile(
     let square_id = commands
         .spawn(square)
         .insert(SquareMarker)
+        .insert(Rounded)
         .insert(Transparency(value.transparency_value()).clone())
         .id();
+    let shadow_id = spawn_shadow(commands, Vec2::splat(TILE_SIZE), Vec2::ZERO, 0.15);
+    commands
+        .entity(shadow_id)
+        .insert(Transparency(value.transparency_value()));
 
     let tile = TileBundle {
         position: pos.clone(),
@@ -172,7 +559,7 @@ fn spawn_tile(
         spatial: SpatialBundle {
             transform: Transform {
                 translation: to_screen(pos).extend(0.0),
-                scale: Vec3::ZERO,
+                scale,
 
                 ..Default::default()
             },
@@ -181,6 +568,7 @@ fn spawn_tile(
     };
     let tile_id = commands
         .spawn(tile)
+        .add_child(shadow_id)
         .add_child(square_id)
         .add_child(text_id)
         .id();
@@ -188,16 +576,15 @@ fn spawn_tile(
     return tile_id;
 }
 
-fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+fn setup(mut commands: Commands, themes: Res<Themes>) {
     commands.spawn(Camera2dBundle::default());
-    let font = asset_server.load(FONT_PATH);
     let headline = Text2dBundle {
         text: Text::from_section(
             "2048",
             TextStyle {
-                font: font.clone(),
+                font: themes.font.clone(),
                 font_size: 80.0,
-                color: Color::BLACK,
+                color: themes.current().text,
             },
         ),
         text_anchor: Anchor::TopCenter,
@@ -207,85 +594,89 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
         },
         ..Default::default()
     };
-    commands.spawn(headline);
-
-    // let mut game = GameState::default();
-    // game.board = Board::from_str("1110000000000000").unwrap();
-    // game.board.spawn_random_tile();
-    // game.board.spawn_random_tile();
-
-    let mut board = Board::from_str("3301100000000010").unwrap();
-    let mut replay = vec![
-        Direction::Right,
-        Direction::Up,
-        Direction::Right,
-        Direction::Up,
-        Direction::Left,
-        Direction::Up,
-        Direction::Up,
-        Direction::Left,
-        Direction::Up,
-        Direction::Left,
-        Direction::Up,
-        Direction::Up,
-    ];
-    replay.reverse();
-
-    let mut entities = HashMap::new();
-    let size = board.size as f32;
+    commands.spawn(headline).insert(Headline).insert(ThemedText);
+}
+
+/// A seed that differs from game to game.
+fn random_seed() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|since| since.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+/// A soft dark copy of a rounded sprite of `size` centered at `center`,
+/// shifted down a little.
+fn spawn_shadow(commands: &mut Commands, size: Vec2, center: Vec2, z: f32) -> Entity {
+    let shadow = SpriteBundle {
+        sprite: Sprite {
+            color: Color::BLACK.with_a(SHADOW_ALPHA),
+            anchor: Anchor::Center,
+            custom_size: Some(size),
+            ..Default::default()
+        },
+        transform: Transform {
+            translation: (center + SHADOW_OFFSET).extend(z),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    commands.spawn(shadow).insert(Shadow).insert(Rounded).id()
+}
+
+/// Spawns the board frame and the empty cells of a `size` x `size` board.
+fn spawn_board(commands: &mut Commands, themes: &Themes, size: usize) -> Entity {
+    let theme = themes.current();
+    let size = size as f32;
     let offset = (TILE_SIZE + TILE_GAP) * (size - 1.0) * 0.5;
     let offset_vec = Vec2::new(-offset, offset);
 
-    let mut tile_ids = Vec::new();
     let mut empty_ids = Vec::new();
-
-    println!("now rendering board...");
-    let traversal = board.traversal_map.get(&Direction::Left).unwrap();
-    for line in traversal.iter() {
-        for pos in line.iter() {
-            let vec = to_screen(pos);
-
+    for row in 0..size as usize {
+        for col in 0..size as usize {
             let empty = SpriteBundle {
                 sprite: Sprite {
-                    color: EMPTY_TILE_BG_COLOR,
+                    color: theme.empty_tile,
                     anchor: Anchor::Center,
-                    rect: Some(Rect {
-                        min: Vec2::new(0.0, 0.0),
-                        max: Vec2::new(TILE_SIZE, TILE_SIZE),
-                    }),
+                    custom_size: Some(Vec2::splat(TILE_SIZE)),
                     ..Default::default()
                 },
                 transform: Transform {
-                    translation: vec.extend(0.1),
+                    translation: to_screen(&Position { row, col }).extend(0.1),
                     ..Default::default()
                 },
                 ..Default::default()
             };
-            empty_ids.push(commands.spawn(empty).id());
-
-            let tile = board.get_tile(pos);
-
-            if let Value::Empty = tile.value {
-                continue;
-            }
-
-            let tile_id = spawn_tile(&mut commands, &font, pos, &tile.value);
-
-            let duration = 0.1;
-            let timer = Timer::from_seconds(duration, TimerMode::Once);
-            commands.entity(tile_id).insert(Animating {
-                timer,
-                animation: Animation::Spawning {
-                    entity: tile_id,
-                    tile: tile.clone(),
-                },
-            });
-
-            entities.insert(pos.clone(), tile_id);
-            tile_ids.push(tile_id);
+            empty_ids.push(commands.spawn(empty).insert(EmptyCell).insert(Rounded).id());
         }
     }
 
+    let frame_size = (TILE_SIZE + TILE_GAP) * size + TILE_GAP;
+    let frame = SpriteBundle {
+        sprite: Sprite {
+            color: theme.board,
+            anchor: Anchor::Center,
+            custom_size: Some(Vec2::splat(frame_size)),
+            ..Default::default()
+        },
+        transform: Transform {
+            translation: Vec2::new(offset, -offset).extend(0.05),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let frame_id = commands
+        .spawn(frame)
+        .insert(BoardBackground)
+        .insert(Rounded)
+        .id();
+    let frame_shadow_id = spawn_shadow(
+        commands,
+        Vec2::splat(frame_size),
+        Vec2::new(offset, -offset),
+        0.04,
+    );
+
     let board_bundle = SpatialBundle {
         transform: Transform {
             translation: offset_vec.extend(0.0),
@@ -293,72 +684,263 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
         },
         ..Default::default()
     };
-    let board_entity = commands
+    commands
         .spawn(board_bundle)
+        .add_child(frame_shadow_id)
+        .add_child(frame_id)
         .push_children(&empty_ids)
-        .push_children(&tile_ids)
-        .id();
+        .id()
+}
+
+/// Tears down the running game, if any, and starts over with an empty
+/// board and two random tiles, or with the board of a puzzle or position.
+#[allow(clippy::too_many_arguments)]
+fn start_new_game(
+    mut commands: Commands,
+    mut themes: ResMut<Themes>,
+    config: Res<AnimationConfig>,
+    settings: Res<Settings>,
+    puzzles: Res<Puzzles>,
+    old_game: Option<ResMut<GameState>>,
+    mut new_games: EventReader<NewGame>,
+    mut finished: EventWriter<GameFinished>,
+) {
+    let Some(new_game) = new_games.read().last() else {
+        return;
+    };
+    if let Some(mut old_game) = old_game {
+        if let Some(game) = old_game.finish() {
+            finished.send(game);
+        }
+        commands.entity(old_game.board_entity).despawn_recursive();
+    }
 
-    // println!("done rendering board, now spawning 2 random tiles...");
-    let game = GameState {
+    // moves are popped from the end
+    let replay = match &new_game.replay {
+        Some(replay) => replay.moves.iter().rev().copied().collect(),
+        None => Vec::new(),
+    };
+    let puzzle = new_game.puzzle.and_then(|index| puzzles.get(index));
+    let rule = new_game.rule(settings.merge);
+    let position = new_game
+        .board
+        .as_ref()
+        .and_then(|board| Board::parse_with(board, rule).ok());
+    let from_position = position.is_some();
+    let (board, spawner, mode) = match (puzzle, position) {
+        (Some(puzzle), _) => (puzzle.board(), puzzle.spawner(), puzzle.mode()),
+        (None, position) => (
+            position
+                .unwrap_or_else(|| {
+                    Board::new(new_game.board_size(settings.board_size)).merging(rule)
+                })
+                .wrapping(new_game.wrap(settings.wrap)),
+            Spawner::seeded(new_game.seed).starting_with(&new_game.spawns),
+            new_game.mode(settings.mode),
+        ),
+    };
+    themes.rule = board.rule;
+    let board_entity = spawn_board(&mut commands, &themes, board.size);
+    let mut game = GameState {
         board,
-        entities,
+        entities: HashMap::new(),
         board_entity,
         deferred_events: Vec::new(),
-        rng: ChaCha8Rng::from_seed([0; 32]),
+        spawner,
+        seed: new_game.seed,
         replay,
+        watching: new_game.replay.is_some(),
+        daily: new_game.daily,
+        puzzle: puzzle.and(new_game.puzzle),
+        queued_moves: VecDeque::new(),
+        history: VecDeque::new(),
+        moves: Vec::new(),
+        score: 0,
+        won: false,
+        finished: false,
+        mode,
+        seconds: 0.0,
+        ended: None,
+        log: ActionLog::default(),
     };
+    // puzzles and positions start from their own board
+    if let Some(puzzle) = puzzle {
+        game.spawn_tiles(&mut commands, &themes);
+        println!("puzzle {}: {}", puzzle.name, game.mode.hud(0, 0.0));
+    } else if from_position {
+        game.spawn_tiles(&mut commands, &themes);
+        println!("new game from {} with seed {}", game.board, game.seed);
+    } else {
+        for _ in 0..2 {
+            if let Some(spawn) = game.spawner.plan(&game.board) {
+                game.play_action(&mut commands, &themes, &config, &spawn, None, true);
+            }
+        }
+        println!("new {} game with seed {}", game.mode, game.seed);
+    }
     commands.insert_resource(game);
 }
 
 fn handle_input(
-    mut commands: Commands,
-    keys: Res<ButtonInput<KeyCode>>,
+    mut actions: EventReader<GameAction>,
     mut game_state: ResMut<GameState>,
-    mut event_writer: EventWriter<Action>,
+    mut new_games: EventWriter<NewGame>,
 ) {
-    if !game_state.deferred_events.is_empty() {
-        return;
-    }
-    let direction = match () {
-        _ if keys.just_pressed(KeyCode::ArrowLeft) => Some(Direction::Left),
-        _ if keys.just_pressed(KeyCode::ArrowRight) => Some(Direction::Right),
-        _ if keys.just_pressed(KeyCode::ArrowUp) => Some(Direction::Up),
-        _ if keys.just_pressed(KeyCode::ArrowDown) => Some(Direction::Down),
-        _ if keys.just_pressed(KeyCode::Space) => {
-            if let Some(direction) = game_state.replay.pop() {
-                Some(direction)
-            } else {
-                None
+    for action in actions.read() {
+        match action {
+            GameAction::Move(direction) if !game_state.watching && game_state.ended.is_none() => {
+                game_state.queue_move(*direction)
+            }
+            GameAction::Move(_) => {}
+            GameAction::ReplayStep => {
+                if let Some(direction) = game_state.replay.pop() {
+                    game_state.queue_move(direction);
+                }
             }
+            GameAction::Restart => match game_state.puzzle {
+                Some(index) => {
+                    new_games.send(NewGame::puzzle(index));
+                }
+                None => {
+                    new_games.send(NewGame::with_seed(random_seed()));
+                }
+            },
+            // handled by undo_move and show_hint
+            GameAction::Undo | GameAction::Hint => {}
         }
-        _ => None,
+    }
+}
+
+/// Puts the board back to where it was before the last move. Tiles are
+/// rebuilt from the board instead of playing the move backwards.
+fn undo_move(
+    mut commands: Commands,
+    themes: Res<Themes>,
+    mut actions: EventReader<GameAction>,
+    mut game: ResMut<GameState>,
+    mut undone: EventWriter<Undone>,
+    tiles: Query<Entity, With<SquareId>>,
+) {
+    if !actions.read().any(|action| *action == GameAction::Undo)
+        || game.watching
+        || game.daily.is_some()
+    {
+        return;
+    }
+    let Some(snapshot) = game.history.pop_back() else {
+        return;
+    };
+    for entity in tiles.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let game = game.as_mut();
+    game.queued_moves.clear();
+    game.deferred_events.clear();
+    game.entities.clear();
+    game.board = snapshot.board;
+    game.score = snapshot.score;
+    game.spawner = snapshot.spawner;
+    if let Some(direction) = game.moves.pop() {
+        undone.send(Undone(direction));
+    }
+    game.spawn_tiles(&mut commands, &themes);
+    println!("undo: {}", game.board);
+}
+
+/// Shows the suggested move above the middle of the board.
+fn show_hint(
+    mut commands: Commands,
+    themes: Res<Themes>,
+    mut actions: EventReader<GameAction>,
+    game: Res<GameState>,
+) {
+    if !actions.read().any(|action| *action == GameAction::Hint) {
+        return;
+    }
+    let text = match game.board.hint() {
+        Some(direction) => format!("try {:?}", direction).to_lowercase(),
+        None => "no moves left".to_string(),
     };
+    let last = (game.board.size - 1) as f32 * (TILE_SIZE + TILE_GAP);
+    let center = Vec2::new(last / 2.0, -last / 2.0);
+    score::spawn_popup(&mut commands, &themes, game.board_entity, text, center);
+}
 
-    if let Some(direction) = direction {
-        let events = game_state.board.plan_slide_and_merge(&direction);
+/// Plays the oldest queued move. Animations that are still running are
+/// fast-forwarded to their end state first, so a new move always starts
+/// immediately.
+#[allow(clippy::too_many_arguments)]
+fn play_queued_move(
+    mut commands: Commands,
+    themes: Res<Themes>,
+    config: Res<AnimationConfig>,
+    mut game_state: ResMut<GameState>,
+    mut event_writer: EventWriter<Action>,
+    mut turns: EventWriter<Turn>,
+    mut tiles: Query<AnimatingTile>,
+    mut texts: Query<&mut Text, With<TextMarker>>,
+) {
+    let Some(direction) = game_state.queued_moves.pop_front() else {
+        return;
+    };
+    for (entity, mut position, mut value, mut transform, mut transparency, animating, text_id) in
+        tiles.iter_mut()
+    {
+        finish_animation(
+            &mut commands,
+            entity,
+            &animating.animation,
+            &mut position,
+            &mut value,
+            &mut transform,
+            &mut transparency,
+            &mut texts,
+            text_id,
+        );
+    }
 
-        event_writer.send_batch(events.iter().cloned());
+    let g = game_state.as_mut();
+    let deferred = std::mem::take(&mut g.deferred_events);
+    for action in deferred.iter() {
+        g.play_action(&mut commands, &themes, &config, action, None, false);
+    }
+    event_writer.send_batch(deferred);
 
-        let mut g = game_state.as_mut();
-        let before: String = g.board.to_string();
-        for event in events.iter() {
-            g.board.apply(event.clone());
+    let events = g.board.plan_slide_and_merge(&direction);
+    if !events.is_empty() {
+        g.remember();
+    }
+    let before: String = g.board.to_string();
+    for event in events.iter() {
+        g.play_action(
+            &mut commands,
+            &themes,
+            &config,
+            event,
+            Some(direction),
+            true,
+        );
+    }
+    event_writer.send_batch(events.iter().cloned());
+    let moved = !events.is_empty();
+    let after: String = g.board.to_string();
+    println!("{} --{}--> {}", before, direction, after);
+    if moved {
+        g.moves.push(direction);
+        if !g.watching {
+            turns.send(Turn(direction));
         }
-        let moved = events.len() > 0;
-        let after: String = g.board.to_string();
-        println!("{} --{}--> {}", before, direction, after);
-        if moved {
-            let spawn = g.board.plan_spawn_random_tile(&mut g.rng);
-            if let Some(spawn) = spawn {
-                g.deferred_events.push(spawn);
-            }
+        let spawn = g.spawner.plan(&g.board);
+        if let Some(spawn) = spawn {
+            g.deferred_events.push(spawn);
         }
     }
 }
 
 fn check_animations(
     mut commands: Commands,
+    themes: Res<Themes>,
+    config: Res<AnimationConfig>,
     mut game: ResMut<GameState>,
     mut event_writer: EventWriter<Action>,
     query: Query<&Animating>,
@@ -367,218 +949,135 @@ fn check_animations(
         return;
     }
     if !game.deferred_events.is_empty() {
-        event_writer.send_batch(game.deferred_events.iter().cloned());
-        game.deferred_events.clear();
+        let deferred = std::mem::take(&mut game.deferred_events);
+        for action in deferred.iter() {
+            game.play_action(&mut commands, &themes, &config, action, None, true);
+        }
+        event_writer.send_batch(deferred);
     }
 }
 
-// fn propagate_transparency_to_children(
-//     mut children: Query<(&Children, &mut Transparency), Changed<Transparency>>,
-//     mut commands: Commands,
-// ) {
-//     for (children, mut transparency) in children.iter_mut() {
-//         for child in children.iter() {
-//             commands.entity(*child).insert(Transparency(transparency.0));
-//         }
-//     }
-// }
-
-fn update_transparency(
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    sprites: Query<(&Transparency, &Handle<ColorMaterial>), Changed<Transparency>>,
-    mut texts: Query<(&Transparency, &mut Text), Changed<Transparency>>,
+fn detect_outcome(
+    mut game: ResMut<GameState>,
+    mut actions: EventReader<Action>,
+    mut outcomes: EventWriter<GameOutcome>,
+    mut finished: EventWriter<GameFinished>,
 ) {
-    for (transparency, handle) in sprites.iter() {
-        let material = materials.get_mut(handle).unwrap();
-        material.color.set_a(transparency.0);
+    let mut played = false;
+    for action in actions.read() {
+        played = true;
+        match action {
+            Action::MergeTiles(..) | Action::MergeThreeTiles(..)
+                if action.score() >= game.board.rule.max_tile() && !game.won =>
+            {
+                game.won = true;
+                outcomes.send(GameOutcome::Won);
+            }
+            _ => {}
+        }
     }
-    for (transparency, mut text) in texts.iter_mut() {
-        for section in &mut text.sections {
-            section.style.color.set_a(transparency.0);
+    // the game is lost once its last spawn is out and nothing moves, that
+    // is right after a move when the spawner of a puzzle has run out
+    if played && game.deferred_events.is_empty() && !game.board.can_move() {
+        outcomes.send(GameOutcome::Lost);
+        if let Some(game) = game.finish() {
+            finished.send(game);
         }
     }
 }
 
-fn start_animate(
-    mut commands: Commands,
-    mut event_reader: EventReader<Action>,
-    asset_server: Res<AssetServer>,
+/// The game running when the app quits is finished too.
+fn finish_on_exit(
     mut game: ResMut<GameState>,
+    mut exits: EventReader<AppExit>,
+    mut finished: EventWriter<GameFinished>,
 ) {
-    let font = asset_server.load(FONT_PATH);
-    for action in event_reader.read() {
-        println!("start animate: {:?}", action);
-        match action {
-            Action::SlideTile(tile, new_pos) => {
-                let duration = 0.1;
-                let timer = Timer::from_seconds(duration, TimerMode::Once);
-                let entity = game.get_entity(&tile.position).unwrap();
-                let tile = game.board.get_tile(&tile.position);
-                commands.entity(*entity).insert(Animating {
-                    timer,
-                    animation: Animation::Sliding {
-                        entity: *entity,
-                        tile: tile,
-                        to: new_pos.clone(),
-                    },
-                });
-            }
-            Action::MergeTiles(tile1, tile2, new_pos, new_val) => {
-                let duration = 0.1;
-                let timer1 = Timer::from_seconds(duration, TimerMode::Once);
-                let timer2 = Timer::from_seconds(duration, TimerMode::Once);
-                let e1 = game.get_entity(&tile1.position).unwrap();
-                let e2 = game.get_entity(&tile2.position).unwrap();
-                let tile1 = game.board.get_tile(&tile1.position);
-                let tile2 = game.board.get_tile(&tile2.position);
-                commands.entity(*e1).insert(Animating {
-                    timer: timer1,
-                    animation: Animation::Merging {
-                        entity1: *e1,
-                        entity2: *e2,
-                        tile1: tile1.clone(),
-                        tile2: tile2.clone(),
-                        to: Tile {
-                            position: new_pos.clone(),
-                            value: new_val.clone(),
-                        },
-                    },
-                });
-                commands.entity(*e2).insert(Animating {
-                    timer: timer2,
-                    animation: Animation::Merging {
-                        entity1: *e1,
-                        entity2: *e2,
-                        tile1: tile1.clone(),
-                        tile2: tile2.clone(),
-                        to: Tile {
-                            position: new_pos.clone(),
-                            value: new_val.clone(),
-                        },
-                    },
-                });
-            }
-            Action::SpawnRandomTile(tile) => {
-                let duration = 0.1;
-                let timer = Timer::from_seconds(duration, TimerMode::Once);
-                let entity = spawn_tile(&mut commands, &font, &tile.position, &tile.value);
-                commands.entity(entity).insert(Animating {
-                    timer,
-                    animation: Animation::Spawning {
-                        entity: entity,
-                        tile: tile.clone(),
-                    },
-                });
-                game.as_mut().entities.insert(tile.position.clone(), entity);
-                game.as_mut().board.apply(action.clone());
-                commands.entity(game.board_entity).add_child(entity);
+    if exits.read().last().is_none() {
+        return;
+    }
+    if let Some(game) = game.finish() {
+        finished.send(game);
+    }
+}
+
+/// Debug builds compare the board with the tile entities whenever the game
+/// settles, i.e. no animations are running and no actions are pending.
+#[cfg(debug_assertions)]
+fn check_consistency(
+    game: Res<GameState>,
+    animating: Query<&Animating>,
+    tiles: Query<(Entity, &Position, &Value), With<SquareId>>,
+    mut checked: Local<Option<usize>>,
+) {
+    if !animating.is_empty() || !game.deferred_events.is_empty() {
+        return;
+    }
+    if *checked == Some(game.log.total) {
+        return;
+    }
+    *checked = Some(game.log.total);
+
+    let tiles: Vec<_> = tiles
+        .iter()
+        .map(|(entity, pos, value)| (entity, *pos, *value))
+        .collect();
+    let issues = consistency::check(&game.board, &game.entities, &tiles);
+    if issues.is_empty() {
+        return;
+    }
+    println!("consistency check failed after {} actions:", game.log.total);
+    for issue in issues.iter() {
+        println!("  {}", issue);
+    }
+    print!("{}", consistency::dump_views(&game.board, &tiles));
+    println!("last {} actions:", game.log.recent.len());
+    for action in game.log.recent.iter() {
+        println!("  {:?}", action);
+    }
+}
+
+/// Tiles pass their transparency on to the square, text, shadow and badge
+/// they are drawn with.
+#[allow(clippy::type_complexity)]
+fn propagate_transparency(
+    tiles: Query<(&Transparency, &Children), (With<SquareId>, Changed<Transparency>)>,
+    mut children: Query<&mut Transparency, Without<SquareId>>,
+) {
+    for (transparency, tile_children) in tiles.iter() {
+        for child in tile_children.iter() {
+            if let Ok(mut child_transparency) = children.get_mut(*child) {
+                child_transparency.0 = transparency.0;
             }
         }
     }
 }
-fn update_animations(
-    mut commands: Commands,
-    time: Res<Time>,
-    mut query: Query<(
-        Entity,
-        &mut Position,
-        &mut Transform,
-        &mut Animating,
-        &SquareId,
-        &TextId,
-    )>,
-    mut squares: Query<(Entity, &SquareMarker, &mut Transparency), Without<TextMarker>>,
-    mut texts: Query<(Entity, &TextMarker, &mut Transparency, &mut Text), Without<SquareMarker>>,
-    mut game: ResMut<GameState>,
+
+/// Turns transparency into alpha. Theme changes reset colors to opaque, so
+/// this also runs for sprites and texts that were recolored.
+#[allow(clippy::type_complexity)]
+fn update_transparency(
+    mut sprites: Query<
+        (&Transparency, &mut Sprite, Has<Shadow>),
+        Or<(Changed<Transparency>, Changed<Sprite>)>,
+    >,
+    mut texts: Query<(&Transparency, &mut Text), Or<(Changed<Transparency>, Changed<Text>)>>,
 ) {
-    for (entity, _, mut transform, mut animating, square_id, text_id) in query.iter_mut() {
-        animating.timer.tick(time.delta());
-        let t = animating.timer.fraction();
-        let animation = &animating.animation;
-        let mut game = game.as_mut();
-        match animation {
-            Animation::Sliding { entity, tile, to } => {
-                let old_pos = transform.translation;
-                let new_pos = to_screen(to).extend(old_pos.z);
-                transform.translation = old_pos.lerp(new_pos, t);
-            }
-            Animation::Merging {
-                entity1,
-                entity2,
-                tile1,
-                tile2,
-                to,
-            } => {
-                let is_target = entity == *entity1;
-                // perform merge animation on the target entity by bouncing the scaling it up and down on the last 20% of the animation
-                // perform fade out animation on the other entity and remove it
-                let old_pos = transform.translation;
-                let new_pos = to_screen(&to.position).extend(old_pos.z);
-                transform.translation = old_pos.lerp(new_pos, t);
-                if is_target {
-                    transform.scale = Vec3::splat(if t > 0.8 {
-                        1.0 + 0.2 * ((((t - 0.8) / 0.2) * std::f32::consts::PI).sin())
-                    } else {
-                        1.0
-                    });
-                } else {
-                    transform.scale = Vec3::splat(if t < 0.8 {
-                        (1.0 - t) / 0.8 * 0.5
-                    } else {
-                        (1.0 - t) / 0.2 * 0.5
-                    });
-                }
-            }
-            Animation::Spawning { entity, tile } => {
-                let old_scale = Vec3::ZERO;
-                let new_scale = Vec3::ONE;
-                transform.scale = old_scale.lerp(new_scale, t);
-            }
+    for (transparency, mut sprite, shadow) in sprites.iter_mut() {
+        let opaque = if shadow { SHADOW_ALPHA } else { 1.0 };
+        let alpha = opaque * (1.0 - transparency.0);
+        if sprite.color.a() != alpha {
+            sprite.color.set_a(alpha);
         }
-        if animating.timer.finished() {
-            commands.entity(entity).remove::<Animating>();
-
-            match animation {
-                Animation::Sliding {
-                    entity,
-                    tile,
-                    to: new_pos,
-                } => {
-                    commands.entity(*entity).insert(new_pos.clone());
-                    game.move_entity(&tile.position, new_pos);
-                }
-                Animation::Merging {
-                    entity1,
-                    entity2,
-                    tile1,
-                    tile2,
-                    to: to,
-                } => {
-                    let new_pos = to.position;
-                    let new_val = to.value;
-                    let is_target = entity == *entity1;
-                    if is_target {
-                        println!("merging {} and {} to {}", tile1.value, tile2.value, new_val);
-                        commands
-                            .entity(*entity1)
-                            .insert(new_pos.clone())
-                            .insert(new_val.clone());
-                        texts.get_mut(text_id.0).unwrap().3.sections[0].value =
-                            new_val.text_value();
-                        game.move_entity(&tile1.position, &new_pos);
-                    } else {
-                        commands
-                            .entity(*entity2)
-                            .remove_children(&[text_id.0, square_id.0])
-                            .despawn();
-                        commands.entity(text_id.0).despawn();
-                        commands.entity(square_id.0).despawn();
-                        game.entities.remove(&tile2.position);
-                    }
-                }
-                Animation::Spawning { entity, tile } => {
-                    transform.scale = Vec3::ONE;
-                }
+    }
+    for (transparency, mut text) in texts.iter_mut() {
+        let alpha = 1.0 - transparency.0;
+        if text
+            .sections
+            .iter()
+            .any(|section| section.style.color.a() != alpha)
+        {
+            for section in &mut text.sections {
+                section.style.color.set_a(alpha);
             }
         }
     }
@@ -595,19 +1094,127 @@ where
     }
 }
 
+const USAGE: &str = "usage: animated_2048 [stats | daily | watch <daily result>]";
+
+/// Runs a command line subcommand instead of the game, returns the exit code.
+fn run_command(command: &str) -> i32 {
+    match command {
+        "stats" => {
+            stats::print_stats();
+            0
+        }
+        "daily" => {
+            daily::print_daily();
+            0
+        }
+        _ => {
+            eprintln!("unknown command {}, {}", command, USAGE);
+            2
+        }
+    }
+}
+
+/// The replay of a shared daily challenge result.
+fn watch_daily(result: &str) -> Result<NewGame, String> {
+    let result: DailyResult = result.parse()?;
+    result.verify()?;
+    println!(
+        "watching the daily challenge of {}: {} with a {} in {} moves",
+        result.date,
+        result.score,
+        result.max_tile,
+        result.moves.len()
+    );
+    Ok(NewGame::watch(
+        result.seed,
+        DAILY_BOARD_SIZE,
+        false,
+        MergeRule::Classic,
+        result.moves,
+    ))
+}
+
 fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins.set(AssetPlugin {
-            file_path: "assets".to_string(),
-            ..default()
-        }))
-        .insert_resource(ClearColor(BG_COLOR))
-        .add_systems(Update, bevy::window::close_on_esc)
-        .add_systems(Startup, setup)
-        .add_systems(PreUpdate, handle_input)
-        .add_systems(StateTransition, start_animate)
-        .add_systems(Update, update_animations)
-        .add_systems(PostUpdate, check_animations)
-        .add_event::<Action>()
-        .run();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let watch = match args.first().map(String::as_str) {
+        None => None,
+        Some("watch") => match watch_daily(args.get(1).map_or("", String::as_str)) {
+            Ok(new_game) => Some(new_game),
+            Err(err) => {
+                eprintln!("{}, {}", err, USAGE);
+                std::process::exit(2);
+            }
+        },
+        Some(command) => std::process::exit(run_command(command)),
+    };
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins.set(AssetPlugin {
+        file_path: "assets".to_string(),
+        ..default()
+    }))
+    .init_resource::<AnimationConfig>()
+    .add_plugins(ThemePlugin)
+    .add_plugins(AccessibilityPlugin)
+    .add_plugins(ScorePlugin)
+    .add_plugins(SoundPlugin)
+    .add_plugins(MenuPlugin)
+    .add_plugins(SettingsPlugin)
+    .add_plugins(InputPlugin)
+    .add_plugins(LayoutPlugin)
+    .add_plugins(ShapePlugin)
+    .add_plugins(EffectsPlugin)
+    .add_plugins(StatsPlugin)
+    .add_plugins(ReplayPlugin)
+    .add_plugins(HighScorePlugin)
+    .add_plugins(DailyPlugin)
+    .add_plugins(ModePlugin)
+    .add_plugins(PuzzlePlugin)
+    .add_plugins(EditorPlugin)
+    .add_systems(Startup, setup)
+    .add_systems(PreUpdate, start_new_game)
+    .add_systems(
+        PreUpdate,
+        (handle_input, undo_move, play_queued_move, show_hint)
+            .chain()
+            .after(start_new_game)
+            .after(InputSet)
+            .run_if(in_state(AppState::Playing).and_then(resource_exists::<GameState>)),
+    )
+    .add_systems(
+        Update,
+        update_animations.run_if(not(in_state(AppState::Paused))),
+    )
+    .add_systems(
+        PostUpdate,
+        (check_animations, detect_outcome, finish_on_exit)
+            .chain()
+            .run_if(resource_exists::<GameState>),
+    )
+    .add_systems(
+        PostUpdate,
+        (propagate_transparency, update_transparency)
+            .chain()
+            .after(ThemeSet),
+    )
+    .add_event::<Action>()
+    .add_event::<GameOutcome>()
+    .add_event::<NewGame>()
+    .add_event::<Turn>()
+    .add_event::<Undone>()
+    .add_event::<GameFinished>();
+
+    #[cfg(debug_assertions)]
+    app.add_systems(
+        PostUpdate,
+        check_consistency
+            .after(check_animations)
+            .run_if(resource_exists::<GameState>),
+    );
+
+    if let Some(new_game) = watch {
+        app.world.send_event(new_game);
+        app.insert_resource(NextState(Some(AppState::Playing)));
+    }
+
+    app.run();
 }
@@ -0,0 +1,914 @@
+use bevy::app::AppExit;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+
+use crate::accessibility::Accessibility;
+use crate::animation::{AnimationConfig, TweenKind};
+use crate::daily::{DailyLog, DAILY_BOARD_SIZE};
+use crate::date::Date;
+use crate::effects::EffectQuality;
+use crate::highscores::{rules, HighScores, Verification};
+use crate::merge::MergeRule;
+use crate::mode::{GameMode, ModeEnd};
+use crate::puzzle::{PuzzleProgress, Puzzles};
+use crate::replay;
+use crate::settings::{Settings, MAX_BOARD_SIZE, MIN_BOARD_SIZE};
+use crate::sound::AudioSettings;
+use crate::stats::{spawn_stats_charts, Stats};
+use crate::theme::Themes;
+use crate::{random_seed, GameOutcome, GameState, NewGame};
+
+const VOLUME_STEP: f32 = 0.1;
+const SPEEDS: [f32; 5] = [0.5, 1.0, 1.5, 2.0, 3.0];
+/// Seconds a single tween can be set to, before the speed applies.
+const TWEEN_DURATIONS: [f32; 6] = [0.0, 0.05, 0.1, 0.15, 0.2, 0.3];
+
+#[derive(States, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum AppState {
+    #[default]
+    MainMenu,
+    Playing,
+    Paused,
+    GameOver,
+    Settings,
+    Stats,
+    HighScores,
+    Puzzles,
+    /// The board editor, which edits the board of a game before it starts.
+    Editor,
+}
+
+impl AppState {
+    pub const MENUS: [AppState; 7] = [
+        AppState::MainMenu,
+        AppState::Paused,
+        AppState::GameOver,
+        AppState::Settings,
+        AppState::Stats,
+        AppState::HighScores,
+        AppState::Puzzles,
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuAction {
+    NewGame,
+    /// Today's daily challenge, or its replay once it was played.
+    Daily,
+    Puzzles,
+    /// Plays the puzzle with this index, once it is unlocked.
+    Puzzle(usize),
+    Editor,
+    /// Back to a running game, from the main menu, the pause menu or after
+    /// winning.
+    Continue,
+    Settings,
+    Stats,
+    HighScores,
+    /// Watches the replay of the high score entry with this index.
+    WatchReplay(usize),
+    MainMenu,
+    Quit,
+    Back,
+    CycleTheme,
+    ToggleContrastText,
+    ToggleBadges,
+    CycleMotion,
+    CycleEffects,
+    CycleVolume,
+    ToggleMute,
+    CycleBoardSize,
+    CycleMode,
+    ToggleWrap,
+    CycleMergeRule,
+    CycleSpeed,
+    /// The next curve for this kind of animation.
+    CycleEasing(TweenKind),
+    CycleDuration(TweenKind),
+}
+
+/// What the game looks like from the menus' point of view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GameStatus {
+    pub running: bool,
+    pub can_move: bool,
+    /// Set when the game mode ended the game.
+    pub ended: Option<ModeEnd>,
+    pub puzzle: bool,
+}
+
+impl GameStatus {
+    fn of(game: Option<&GameState>) -> Self {
+        GameStatus {
+            running: game.is_some(),
+            can_move: game.is_some_and(|game| game.board.can_move()),
+            ended: game.and_then(|game| game.ended),
+            puzzle: game.is_some_and(|game| game.puzzle.is_some()),
+        }
+    }
+}
+
+pub fn menu_items(state: AppState, status: GameStatus) -> Vec<MenuAction> {
+    let can_continue = status.running && status.can_move && status.ended.is_none();
+    match state {
+        AppState::MainMenu => {
+            let mut items = vec![];
+            if can_continue {
+                items.push(MenuAction::Continue);
+            }
+            items.extend([
+                MenuAction::NewGame,
+                MenuAction::Daily,
+                MenuAction::Puzzles,
+                MenuAction::Editor,
+                MenuAction::Settings,
+                MenuAction::Stats,
+                MenuAction::HighScores,
+                MenuAction::Quit,
+            ]);
+            items
+        }
+        AppState::Paused => vec![
+            MenuAction::Continue,
+            MenuAction::NewGame,
+            MenuAction::Daily,
+            MenuAction::Puzzles,
+            MenuAction::Editor,
+            MenuAction::Settings,
+            MenuAction::Stats,
+            MenuAction::HighScores,
+            MenuAction::MainMenu,
+        ],
+        AppState::GameOver => {
+            let mut items = vec![];
+            if can_continue {
+                items.push(MenuAction::Continue);
+            }
+            if status.puzzle {
+                items.push(MenuAction::Puzzles);
+            }
+            items.extend([MenuAction::NewGame, MenuAction::MainMenu]);
+            items
+        }
+        AppState::Settings => {
+            let mut items = vec![
+                MenuAction::CycleTheme,
+                MenuAction::ToggleContrastText,
+                MenuAction::ToggleBadges,
+                MenuAction::CycleMotion,
+                MenuAction::CycleEffects,
+                MenuAction::CycleVolume,
+                MenuAction::ToggleMute,
+                MenuAction::CycleBoardSize,
+                MenuAction::CycleMode,
+                MenuAction::ToggleWrap,
+                MenuAction::CycleMergeRule,
+                MenuAction::CycleSpeed,
+            ];
+            for kind in TweenKind::ALL {
+                items.extend([
+                    MenuAction::CycleEasing(kind),
+                    MenuAction::CycleDuration(kind),
+                ]);
+            }
+            items.push(MenuAction::Back);
+            items
+        }
+        // high score entries come from `highscore_items`, puzzles from
+        // `puzzle_items`
+        AppState::Stats | AppState::HighScores | AppState::Puzzles => vec![MenuAction::Back],
+        AppState::Playing | AppState::Editor => vec![],
+    }
+}
+
+pub fn menu_title(state: AppState, status: GameStatus) -> &'static str {
+    match state {
+        AppState::MainMenu => "2048",
+        AppState::Paused => "paused",
+        AppState::GameOver => match status.ended {
+            Some(ModeEnd::TimeUp) => "time's up",
+            Some(ModeEnd::OutOfMoves) => "out of moves",
+            Some(ModeEnd::TargetReached) if status.puzzle => "solved!",
+            Some(ModeEnd::TargetReached) => "target reached!",
+            None if status.can_move => "you win!",
+            None => "game over",
+        },
+        AppState::Settings => "settings",
+        AppState::Stats => "statistics",
+        AppState::HighScores => "high scores",
+        AppState::Puzzles => "puzzles",
+        AppState::Playing | AppState::Editor => "",
+    }
+}
+
+/// Where Esc leads from `state`. `None` quits the game.
+pub fn escape_target(state: AppState, settings_return: AppState) -> Option<AppState> {
+    match state {
+        AppState::MainMenu => None,
+        AppState::Playing => Some(AppState::Paused),
+        AppState::Paused => Some(AppState::Playing),
+        AppState::GameOver | AppState::Editor => Some(AppState::MainMenu),
+        AppState::Settings | AppState::Stats | AppState::HighScores | AppState::Puzzles => {
+            Some(settings_return)
+        }
+    }
+}
+
+fn on_off(on: bool) -> &'static str {
+    if on {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+fn label(action: MenuAction, settings: &MenuSettings) -> String {
+    let accessibility = &settings.accessibility;
+    let audio = &settings.audio;
+    match action {
+        MenuAction::NewGame => "new game".to_string(),
+        MenuAction::Daily => match settings.daily.get(Date::today()) {
+            Some(result) => format!("daily challenge: {} (watch)", result.score),
+            None => "daily challenge".to_string(),
+        },
+        MenuAction::Puzzles => "puzzles".to_string(),
+        MenuAction::Editor => "board editor".to_string(),
+        MenuAction::Puzzle(index) => puzzle_label(&settings.puzzles, &settings.progress, index),
+        MenuAction::Continue => "continue".to_string(),
+        MenuAction::Settings => "settings".to_string(),
+        MenuAction::Stats => "statistics".to_string(),
+        MenuAction::HighScores => "high scores".to_string(),
+        MenuAction::WatchReplay(index) => highscore_label(&settings.highscores, index),
+        MenuAction::MainMenu => "main menu".to_string(),
+        MenuAction::Quit => "quit".to_string(),
+        MenuAction::Back => "back".to_string(),
+        MenuAction::CycleTheme => format!("theme: {}", settings.themes.current().name),
+        MenuAction::ToggleContrastText => {
+            format!("contrast text: {}", on_off(accessibility.contrast_text))
+        }
+        MenuAction::ToggleBadges => format!("badges: {}", on_off(accessibility.badges)),
+        MenuAction::CycleMotion => format!("motion: {:?}", accessibility.motion).to_lowercase(),
+        MenuAction::CycleEffects => format!("effects: {:?}", *settings.effects).to_lowercase(),
+        MenuAction::CycleVolume => format!("volume: {:.0}%", audio.volume * 100.0),
+        MenuAction::ToggleMute => format!("muted: {}", on_off(audio.muted)),
+        MenuAction::CycleBoardSize => {
+            let size = settings.settings.board_size;
+            format!("board: {}x{} (next game)", size, size)
+        }
+        MenuAction::CycleMode => format!("mode: {} (next game)", settings.settings.mode.name()),
+        MenuAction::ToggleWrap => {
+            format!(
+                "wrap around: {} (next game)",
+                on_off(settings.settings.wrap)
+            )
+        }
+        MenuAction::CycleMergeRule => {
+            format!("merge rule: {} (next game)", settings.settings.merge)
+        }
+        MenuAction::CycleSpeed => format!("speed: {}x", settings.animation.speed),
+        MenuAction::CycleEasing(kind) => format!(
+            "{} curve: {}",
+            kind.name(),
+            settings.animation.tweens.get(kind).easing.name()
+        ),
+        MenuAction::CycleDuration(kind) => format!(
+            "{} time: {:.2}s",
+            kind.name(),
+            settings.animation.tweens.get(kind).duration
+        ),
+    }
+}
+
+/// One item per entry of the table of `board_size` and `mode`, on wrapping
+/// boards or not, with merge `rule`.
+pub fn highscore_items(
+    highscores: &HighScores,
+    board_size: usize,
+    mode: GameMode,
+    wrap: bool,
+    rule: MergeRule,
+) -> Vec<MenuAction> {
+    highscores
+        .table(board_size, &rules(mode, wrap, rule))
+        .into_iter()
+        .map(MenuAction::WatchReplay)
+        .collect()
+}
+
+fn highscore_label(highscores: &HighScores, index: usize) -> String {
+    let Some((entry, verification)) = highscores.get(index) else {
+        return String::new();
+    };
+    let rank = highscores
+        .table(entry.board_size, &entry.rules)
+        .iter()
+        .position(|other| *other == index)
+        .unwrap_or_default();
+    let edited = if verification == Verification::Valid {
+        ""
+    } else {
+        " (edited)"
+    };
+    format!(
+        "{}. {} {}{}",
+        rank + 1,
+        entry.name,
+        entry.mode().result(entry),
+        edited
+    )
+}
+
+/// One item per puzzle, locked ones included.
+pub fn puzzle_items(puzzles: &Puzzles) -> Vec<MenuAction> {
+    (0..puzzles.0.len()).map(MenuAction::Puzzle).collect()
+}
+
+fn puzzle_label(puzzles: &Puzzles, progress: &PuzzleProgress, index: usize) -> String {
+    let Some(puzzle) = puzzles.get(index) else {
+        return String::new();
+    };
+    if !progress.is_unlocked(puzzles, index) {
+        return format!("{}. locked", index + 1);
+    }
+    let goal = format!("{}. {}: {}", index + 1, puzzle.name, puzzle.mode().name());
+    match progress.best(puzzle) {
+        Some(best) => format!("{}, best {} (par {})", goal, best, puzzle.par),
+        None => format!("{} (par {})", goal, puzzle.par),
+    }
+}
+
+/// Steps through 0%, 10%, ... 100% and back to 0%.
+pub fn next_volume(volume: f32) -> f32 {
+    let step = (volume / VOLUME_STEP).round() as u32 + 1;
+    if step as f32 * VOLUME_STEP > 1.0 + f32::EPSILON {
+        0.0
+    } else {
+        step as f32 * VOLUME_STEP
+    }
+}
+
+pub fn next_board_size(size: usize) -> usize {
+    if size >= MAX_BOARD_SIZE {
+        MIN_BOARD_SIZE
+    } else {
+        size + 1
+    }
+}
+
+/// The next faster entry of `SPEEDS`, after the fastest the slowest.
+pub fn next_speed(speed: f32) -> f32 {
+    SPEEDS
+        .iter()
+        .copied()
+        .find(|step| *step > speed + f32::EPSILON)
+        .unwrap_or(SPEEDS[0])
+}
+
+/// The next longer entry of `TWEEN_DURATIONS`, after the longest none.
+pub fn next_tween_duration(duration: f32) -> f32 {
+    TWEEN_DURATIONS
+        .iter()
+        .copied()
+        .find(|step| *step > duration + f32::EPSILON)
+        .unwrap_or(TWEEN_DURATIONS[0])
+}
+
+/// Index of the highlighted item in the open menu.
+#[derive(Resource, Debug, Default)]
+pub struct MenuSelection(pub usize);
+
+/// The menu the settings and stats screens return to.
+#[derive(Resource, Debug)]
+pub struct SettingsReturn(pub AppState);
+
+impl Default for SettingsReturn {
+    fn default() -> Self {
+        SettingsReturn(AppState::MainMenu)
+    }
+}
+
+/// The settings the settings menu shows and changes, the high scores, the
+/// daily challenge and the puzzles.
+#[derive(SystemParam)]
+pub struct MenuSettings<'w> {
+    asset_server: Res<'w, AssetServer>,
+    themes: ResMut<'w, Themes>,
+    accessibility: ResMut<'w, Accessibility>,
+    audio: ResMut<'w, AudioSettings>,
+    animation: ResMut<'w, AnimationConfig>,
+    effects: ResMut<'w, EffectQuality>,
+    settings: ResMut<'w, Settings>,
+    highscores: Res<'w, HighScores>,
+    daily: Res<'w, DailyLog>,
+    puzzles: Res<'w, Puzzles>,
+    progress: Res<'w, PuzzleProgress>,
+}
+
+/// Everything a menu item can do besides changing settings.
+#[derive(SystemParam)]
+pub struct MenuFlow<'w> {
+    next_state: ResMut<'w, NextState<AppState>>,
+    settings_return: ResMut<'w, SettingsReturn>,
+    new_games: EventWriter<'w, NewGame>,
+    exit: EventWriter<'w, AppExit>,
+}
+
+#[derive(Component)]
+pub struct MenuRoot;
+
+#[derive(Component)]
+pub struct MenuButton {
+    index: usize,
+    action: MenuAction,
+}
+
+#[derive(Component)]
+pub struct MenuLabel(MenuAction);
+
+#[derive(Component)]
+pub struct MenuTitle;
+
+fn spawn_menu(
+    mut commands: Commands,
+    state: Res<State<AppState>>,
+    game: Option<Res<GameState>>,
+    settings: MenuSettings,
+    stats: Res<Stats>,
+    mut selection: ResMut<MenuSelection>,
+) {
+    let status = GameStatus::of(game.as_deref());
+    let theme = settings.themes.current();
+    selection.0 = 0;
+    let text_style = |font_size: f32| TextStyle {
+        font: settings.themes.font.clone(),
+        font_size,
+        color: theme.text,
+    };
+    let root = NodeBundle {
+        style: Style {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            row_gap: Val::Px(12.0),
+            ..Default::default()
+        },
+        background_color: theme.background.with_a(0.85).into(),
+        ..Default::default()
+    };
+    commands
+        .spawn(root)
+        .insert(MenuRoot)
+        .with_children(|parent| {
+            parent
+                .spawn(TextBundle::from_section(
+                    menu_title(*state.get(), status),
+                    text_style(60.0),
+                ))
+                .insert(MenuTitle);
+            let mut items = menu_items(*state.get(), status);
+            if *state.get() == AppState::Stats {
+                spawn_stats_charts(parent, &stats, theme, &settings.themes.font);
+            }
+            if *state.get() == AppState::HighScores {
+                let size = settings.settings.board_size;
+                let mode = settings.settings.mode;
+                let wrap = settings.settings.wrap;
+                let rule = settings.settings.merge;
+                let entries = highscore_items(&settings.highscores, size, mode, wrap, rule);
+                let mut kind = mode.name();
+                if rule != MergeRule::Classic {
+                    kind = format!("{} {}", kind, rule);
+                }
+                if wrap {
+                    kind = format!("{} wrapping", kind);
+                }
+                let table = if entries.is_empty() {
+                    format!("no {} games on {}x{} yet", kind, size, size)
+                } else {
+                    format!("{} {}x{}, select one to watch it", kind, size, size)
+                };
+                parent.spawn(TextBundle::from_section(table, text_style(24.0)));
+                items.splice(0..0, entries);
+            }
+            if *state.get() == AppState::Puzzles {
+                let hint = "solve a puzzle to unlock the next one";
+                parent.spawn(TextBundle::from_section(hint, text_style(24.0)));
+                items.splice(0..0, puzzle_items(&settings.puzzles));
+            }
+            for (index, action) in items.into_iter().enumerate() {
+                let button = ButtonBundle {
+                    style: Style {
+                        width: Val::Px(320.0),
+                        height: Val::Px(56.0),
+                        align_items: AlignItems::Center,
+                        justify_content: JustifyContent::Center,
+                        ..Default::default()
+                    },
+                    background_color: theme.empty_tile.into(),
+                    ..Default::default()
+                };
+                parent
+                    .spawn(button)
+                    .insert(MenuButton { index, action })
+                    .with_children(|button| {
+                        let text = label(action, &settings);
+                        button
+                            .spawn(TextBundle::from_section(text, text_style(28.0)))
+                            .insert(MenuLabel(action));
+                    });
+            }
+        });
+}
+
+fn despawn_menu(mut commands: Commands, menus: Query<Entity, With<MenuRoot>>) {
+    for menu in menus.iter() {
+        commands.entity(menu).despawn_recursive();
+    }
+}
+
+fn run_menu_action(
+    action: MenuAction,
+    state: AppState,
+    flow: &mut MenuFlow,
+    settings: &mut MenuSettings,
+) {
+    match action {
+        MenuAction::NewGame => {
+            flow.new_games.send(NewGame::with_seed(random_seed()));
+            flow.next_state.set(AppState::Playing);
+        }
+        MenuAction::Daily => {
+            let today = Date::today();
+            let new_game = match settings.daily.get(today) {
+                Some(result) => NewGame::watch(
+                    result.seed,
+                    DAILY_BOARD_SIZE,
+                    false,
+                    MergeRule::Classic,
+                    result.moves.clone(),
+                ),
+                None => NewGame::daily(today),
+            };
+            flow.new_games.send(new_game);
+            flow.next_state.set(AppState::Playing);
+        }
+        MenuAction::Puzzles => {
+            flow.settings_return.0 = state;
+            flow.next_state.set(AppState::Puzzles);
+        }
+        MenuAction::Puzzle(index) => {
+            if !settings.progress.is_unlocked(&settings.puzzles, index) {
+                return;
+            }
+            flow.new_games.send(NewGame::puzzle(index));
+            flow.next_state.set(AppState::Playing);
+        }
+        MenuAction::Editor => flow.next_state.set(AppState::Editor),
+        MenuAction::Continue => flow.next_state.set(AppState::Playing),
+        MenuAction::Settings => {
+            flow.settings_return.0 = state;
+            flow.next_state.set(AppState::Settings);
+        }
+        MenuAction::Stats => {
+            flow.settings_return.0 = state;
+            flow.next_state.set(AppState::Stats);
+        }
+        MenuAction::HighScores => {
+            flow.settings_return.0 = state;
+            flow.next_state.set(AppState::HighScores);
+        }
+        MenuAction::WatchReplay(index) => {
+            let Some((entry, _)) = settings.highscores.get(index) else {
+                return;
+            };
+            // verified again, the file may have changed since it was loaded
+            let verification = entry.verify();
+            if verification != Verification::Valid {
+                warn!(
+                    "not replaying {}'s {}: {:?}",
+                    entry.name, entry.score, verification
+                );
+                return;
+            }
+            let moves = replay::decode(&entry.replay).unwrap_or_default();
+            flow.new_games.send(NewGame::watch(
+                entry.seed,
+                entry.board_size,
+                entry.wraps(),
+                entry.merge_rule(),
+                moves,
+            ));
+            flow.next_state.set(AppState::Playing);
+        }
+        MenuAction::MainMenu => flow.next_state.set(AppState::MainMenu),
+        MenuAction::Quit => {
+            flow.exit.send(AppExit);
+        }
+        MenuAction::Back => flow.next_state.set(flow.settings_return.0),
+        MenuAction::CycleTheme => {
+            let next = settings.themes.current + 1;
+            settings.themes.select(next, &settings.asset_server);
+        }
+        MenuAction::ToggleContrastText => {
+            settings.accessibility.contrast_text = !settings.accessibility.contrast_text;
+        }
+        MenuAction::ToggleBadges => settings.accessibility.badges = !settings.accessibility.badges,
+        MenuAction::CycleMotion => {
+            settings.accessibility.motion = settings.accessibility.motion.next();
+        }
+        MenuAction::CycleEffects => *settings.effects = settings.effects.next(),
+        MenuAction::CycleVolume => settings.audio.volume = next_volume(settings.audio.volume),
+        MenuAction::ToggleMute => settings.audio.muted = !settings.audio.muted,
+        MenuAction::CycleBoardSize => {
+            settings.settings.board_size = next_board_size(settings.settings.board_size);
+        }
+        MenuAction::CycleMode => settings.settings.mode = settings.settings.mode.next(),
+        MenuAction::ToggleWrap => settings.settings.wrap = !settings.settings.wrap,
+        MenuAction::CycleMergeRule => settings.settings.merge = settings.settings.merge.next(),
+        MenuAction::CycleSpeed => settings.animation.speed = next_speed(settings.animation.speed),
+        MenuAction::CycleEasing(kind) => {
+            let tween = settings.animation.tweens.get_mut(kind);
+            tween.easing = tween.easing.next();
+        }
+        MenuAction::CycleDuration(kind) => {
+            let tween = settings.animation.tweens.get_mut(kind);
+            tween.duration = next_tween_duration(tween.duration);
+        }
+    }
+}
+
+/// Moves the highlight with the arrow keys or the mouse and runs the
+/// highlighted item on Enter, Space or click.
+fn navigate_menu(
+    keys: Res<ButtonInput<KeyCode>>,
+    state: Res<State<AppState>>,
+    mut selection: ResMut<MenuSelection>,
+    buttons: Query<&MenuButton>,
+    interactions: Query<(&MenuButton, &Interaction), Changed<Interaction>>,
+    mut flow: MenuFlow,
+    mut settings: MenuSettings,
+) {
+    let count = buttons.iter().count();
+    if count == 0 {
+        return;
+    }
+    if keys.just_pressed(KeyCode::ArrowDown) {
+        selection.0 = (selection.0 + 1) % count;
+    }
+    if keys.just_pressed(KeyCode::ArrowUp) {
+        selection.0 = (selection.0 + count - 1) % count;
+    }
+    let mut chosen = None;
+    for (button, interaction) in interactions.iter() {
+        match interaction {
+            Interaction::Pressed => chosen = Some(button.action),
+            Interaction::Hovered => selection.0 = button.index,
+            Interaction::None => {}
+        }
+    }
+    if keys.any_just_pressed([KeyCode::Enter, KeyCode::Space]) {
+        chosen = buttons
+            .iter()
+            .find(|button| button.index == selection.0)
+            .map(|button| button.action);
+    }
+    if let Some(action) = chosen {
+        run_menu_action(action, *state.get(), &mut flow, &mut settings);
+    }
+}
+
+/// Keeps labels and the highlight in sync with the settings they show.
+#[allow(clippy::type_complexity)]
+fn update_menu(
+    settings: MenuSettings,
+    selection: Res<MenuSelection>,
+    mut buttons: Query<(&MenuButton, &mut BackgroundColor), Without<MenuRoot>>,
+    mut roots: Query<&mut BackgroundColor, With<MenuRoot>>,
+    mut labels: Query<(&MenuLabel, &mut Text), Without<MenuTitle>>,
+    mut titles: Query<&mut Text, With<MenuTitle>>,
+) {
+    let themes = &settings.themes;
+    let theme = themes.current();
+    if themes.is_changed() {
+        for mut background in roots.iter_mut() {
+            background.0 = theme.background.with_a(0.85);
+        }
+        for mut text in titles.iter_mut() {
+            text.sections[0].style.color = theme.text;
+            text.sections[0].style.font = themes.font.clone();
+        }
+    }
+    for (button, mut background) in buttons.iter_mut() {
+        let color = if button.index == selection.0 {
+            theme.board
+        } else {
+            theme.empty_tile
+        };
+        if background.0 != color {
+            background.0 = color;
+        }
+    }
+    for (MenuLabel(action), mut text) in labels.iter_mut() {
+        let value = label(*action, &settings);
+        if text.sections[0].value != value || text.sections[0].style.color != theme.text {
+            text.sections[0].value = value;
+            text.sections[0].style.color = theme.text;
+            text.sections[0].style.font = themes.font.clone();
+        }
+    }
+}
+
+fn handle_escape(
+    keys: Res<ButtonInput<KeyCode>>,
+    state: Res<State<AppState>>,
+    settings_return: Res<SettingsReturn>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if !keys.just_pressed(KeyCode::Escape) {
+        return;
+    }
+    match escape_target(*state.get(), settings_return.0) {
+        Some(target) => next_state.set(target),
+        None => {
+            exit.send(AppExit);
+        }
+    }
+}
+
+fn enter_game_over(
+    mut outcomes: EventReader<GameOutcome>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if outcomes.read().last().is_some() {
+        next_state.set(AppState::GameOver);
+    }
+}
+
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<AppState>()
+            .init_resource::<MenuSelection>()
+            .init_resource::<SettingsReturn>()
+            .add_systems(Update, handle_escape)
+            .add_systems(Update, enter_game_over.run_if(in_state(AppState::Playing)))
+            .add_systems(
+                Update,
+                (navigate_menu, update_menu)
+                    .chain()
+                    .run_if(not(in_state(AppState::Playing))),
+            );
+        for state in AppState::MENUS {
+            app.add_systems(OnEnter(state), spawn_menu)
+                .add_systems(OnExit(state), despawn_menu);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NO_GAME: GameStatus = GameStatus {
+        running: false,
+        can_move: false,
+        ended: None,
+        puzzle: false,
+    };
+    const RUNNING: GameStatus = GameStatus {
+        running: true,
+        can_move: true,
+        ended: None,
+        puzzle: false,
+    };
+    const LOST: GameStatus = GameStatus {
+        running: true,
+        can_move: false,
+        ended: None,
+        puzzle: false,
+    };
+    const TIME_UP: GameStatus = GameStatus {
+        running: true,
+        can_move: true,
+        ended: Some(ModeEnd::TimeUp),
+        puzzle: false,
+    };
+    const SOLVED: GameStatus = GameStatus {
+        running: true,
+        can_move: true,
+        ended: Some(ModeEnd::TargetReached),
+        puzzle: true,
+    };
+
+    #[test]
+    fn test_continue_needs_a_running_game() {
+        assert_eq!(
+            menu_items(AppState::MainMenu, NO_GAME)[0],
+            MenuAction::NewGame
+        );
+        assert_eq!(
+            menu_items(AppState::MainMenu, RUNNING)[0],
+            MenuAction::Continue
+        );
+        assert_eq!(menu_items(AppState::MainMenu, LOST)[0], MenuAction::NewGame);
+        assert_eq!(
+            menu_items(AppState::GameOver, RUNNING)[0],
+            MenuAction::Continue
+        );
+        assert!(!menu_items(AppState::GameOver, LOST).contains(&MenuAction::Continue));
+    }
+
+    #[test]
+    fn test_every_menu_has_a_way_out() {
+        for state in AppState::MENUS {
+            for status in [NO_GAME, RUNNING, LOST, TIME_UP, SOLVED] {
+                let items = menu_items(state, status);
+                assert!(!items.is_empty(), "{:?}", state);
+                assert!(
+                    items.iter().any(|item| matches!(
+                        item,
+                        MenuAction::Continue
+                            | MenuAction::NewGame
+                            | MenuAction::Back
+                            | MenuAction::MainMenu
+                    )),
+                    "{:?}",
+                    state
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_game_over_title() {
+        assert_eq!(menu_title(AppState::GameOver, RUNNING), "you win!");
+        assert_eq!(menu_title(AppState::GameOver, LOST), "game over");
+        assert_eq!(menu_title(AppState::GameOver, TIME_UP), "time's up");
+        assert_eq!(menu_title(AppState::GameOver, SOLVED), "solved!");
+        assert_eq!(
+            menu_items(AppState::GameOver, SOLVED)[0],
+            MenuAction::Puzzles
+        );
+        // a game its mode ended cannot go on
+        assert!(!menu_items(AppState::GameOver, TIME_UP).contains(&MenuAction::Continue));
+        assert!(!menu_items(AppState::MainMenu, TIME_UP).contains(&MenuAction::Continue));
+    }
+
+    #[test]
+    fn test_escape_targets() {
+        assert_eq!(
+            escape_target(AppState::Playing, AppState::MainMenu),
+            Some(AppState::Paused)
+        );
+        assert_eq!(
+            escape_target(AppState::Paused, AppState::MainMenu),
+            Some(AppState::Playing)
+        );
+        assert_eq!(
+            escape_target(AppState::Settings, AppState::Paused),
+            Some(AppState::Paused)
+        );
+        assert_eq!(
+            escape_target(AppState::Stats, AppState::MainMenu),
+            Some(AppState::MainMenu)
+        );
+        assert_eq!(
+            escape_target(AppState::HighScores, AppState::Paused),
+            Some(AppState::Paused)
+        );
+        assert_eq!(
+            escape_target(AppState::Editor, AppState::Paused),
+            Some(AppState::MainMenu)
+        );
+        assert_eq!(escape_target(AppState::MainMenu, AppState::MainMenu), None);
+    }
+
+    #[test]
+    fn test_volume_steps_wrap_around() {
+        let mut volume = 0.0;
+        let mut steps = vec![];
+        for _ in 0..12 {
+            volume = next_volume(volume);
+            steps.push((volume * 100.0).round() as u32);
+        }
+        assert_eq!(steps, vec![10, 20, 30, 40, 50, 60, 70, 80, 90, 100, 0, 10]);
+        assert!((next_volume(0.7) - 0.8).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_board_sizes_and_speeds_wrap_around() {
+        assert_eq!(next_board_size(4), 5);
+        assert_eq!(next_board_size(MAX_BOARD_SIZE), MIN_BOARD_SIZE);
+        assert_eq!(next_speed(1.0), 1.5);
+        assert_eq!(next_speed(3.0), 0.5);
+        // speeds from a hand edited settings file join the cycle
+        assert_eq!(next_speed(1.2), 1.5);
+        assert_eq!(next_tween_duration(0.1), 0.15);
+        assert_eq!(next_tween_duration(0.3), 0.0);
+        assert_eq!(next_tween_duration(0.12), 0.15);
+    }
+}
@@ -0,0 +1,242 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::tile::value::Value;
+
+/// How many values a rule knows, from the smallest tile to the one that
+/// wins the game.
+pub const LADDER_LEN: usize = 11;
+
+/// Which tiles combine when they meet. Every rule has a ladder of values
+/// that takes the place of 2 to 2048: a value is written with the board
+/// notation and colored like the classic tile on the same rung.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MergeRule {
+    /// Two equal tiles make their sum.
+    #[default]
+    Classic,
+    /// Neighbours in the Fibonacci sequence make the next number, 1 and 1
+    /// make 2.
+    Fibonacci,
+    /// A 1 and a 2 make 3, from there on two equal tiles make their sum.
+    Threes,
+    /// Three equal tiles make their sum.
+    Triples,
+}
+
+impl MergeRule {
+    pub const ALL: [MergeRule; 4] = [
+        MergeRule::Classic,
+        MergeRule::Fibonacci,
+        MergeRule::Threes,
+        MergeRule::Triples,
+    ];
+
+    pub fn next(&self) -> MergeRule {
+        let index = MergeRule::ALL
+            .iter()
+            .position(|rule| rule == self)
+            .unwrap_or_default();
+        MergeRule::ALL[(index + 1) % MergeRule::ALL.len()]
+    }
+
+    pub fn ladder(&self) -> [u32; LADDER_LEN] {
+        match self {
+            MergeRule::Classic => [2, 4, 8, 16, 32, 64, 128, 256, 512, 1024, 2048],
+            MergeRule::Fibonacci => [1, 2, 3, 5, 8, 13, 21, 34, 55, 89, 144],
+            MergeRule::Threes => [1, 2, 3, 6, 12, 24, 48, 96, 192, 384, 768],
+            MergeRule::Triples => [1, 3, 9, 27, 81, 243, 729, 2187, 6561, 19683, 59049],
+        }
+    }
+
+    /// The value that wins the game, nothing merges past it.
+    pub fn max_tile(&self) -> u32 {
+        self.ladder()[LADDER_LEN - 1]
+    }
+
+    /// The rung of `value` on the ladder, counted from 0.
+    pub fn rank(&self, value: &Value) -> Option<usize> {
+        match value {
+            Value::Number(n) => self.ladder().iter().position(|rung| rung == n),
+            Value::Empty | Value::Wall => None,
+        }
+    }
+
+    /// How many tiles merge at once.
+    pub fn group(&self) -> usize {
+        match self {
+            MergeRule::Triples => 3,
+            _ => 2,
+        }
+    }
+
+    /// Whether `values`, neighbours in the order they slide, merge into
+    /// their sum.
+    pub fn merges(&self, values: &[u32]) -> bool {
+        let ladder = self.ladder();
+        if values.len() != self.group()
+            || !values.iter().all(|value| ladder.contains(value))
+            || !ladder.contains(&values.iter().sum())
+        {
+            return false;
+        }
+        let equal = values.iter().all(|value| *value == values[0]);
+        match self {
+            MergeRule::Classic | MergeRule::Triples => equal,
+            MergeRule::Fibonacci => true,
+            MergeRule::Threes => matches!(values, [1, 2] | [2, 1]) || (equal && values[0] >= 3),
+        }
+    }
+
+    /// The two values new tiles get, and how likely the first one is.
+    pub fn spawns(&self) -> (u32, u32, f64) {
+        let ladder = self.ladder();
+        let common = match self {
+            MergeRule::Threes => 0.5,
+            _ => 0.9,
+        };
+        (ladder[0], ladder[1], common)
+    }
+
+    /// The classic tile on the same rung as `value`, which is what themes
+    /// color and badges number. Walls, empty cells and values off the
+    /// ladder stay as they are.
+    pub fn classic(&self, value: &Value) -> Value {
+        match self.rank(value) {
+            Some(rank) => Value::Number(MergeRule::Classic.ladder()[rank]),
+            None => *value,
+        }
+    }
+
+    /// The value the classic tile `value` stands for under this rule.
+    pub fn for_classic(&self, value: Value) -> Value {
+        match MergeRule::Classic.rank(&value) {
+            Some(rank) => Value::Number(self.ladder()[rank]),
+            None => value,
+        }
+    }
+
+    /// `value` in board notation, `'1'` for the first rung up to `'B'`.
+    pub fn notation(&self, value: &Value) -> String {
+        self.classic(value).to_string()
+    }
+
+    /// The value written as `notation`.
+    pub fn parse(&self, notation: &str) -> Option<Value> {
+        notation.parse().ok().map(|value| self.for_classic(value))
+    }
+}
+
+/// The names high score tables and settings use.
+impl fmt::Display for MergeRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MergeRule::Classic => write!(f, "classic"),
+            MergeRule::Fibonacci => write!(f, "fibonacci"),
+            MergeRule::Threes => write!(f, "threes"),
+            MergeRule::Triples => write!(f, "triples"),
+        }
+    }
+}
+
+impl FromStr for MergeRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        MergeRule::ALL
+            .into_iter()
+            .find(|rule| rule.to_string() == s)
+            .ok_or_else(|| format!("unknown merge rule {}", s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classic_merges_equal_tiles() {
+        let rule = MergeRule::Classic;
+        assert!(rule.merges(&[2, 2]));
+        assert!(rule.merges(&[1024, 1024]));
+        assert!(!rule.merges(&[2, 4]));
+        assert!(!rule.merges(&[2048, 2048]));
+        assert_eq!(rule.spawns(), (2, 4, 0.9));
+        assert_eq!(rule.max_tile(), 2048);
+    }
+
+    #[test]
+    fn test_fibonacci_merges_neighbours() {
+        let rule = MergeRule::Fibonacci;
+        assert!(rule.merges(&[1, 1]));
+        assert!(rule.merges(&[1, 2]));
+        assert!(rule.merges(&[5, 3]));
+        assert!(rule.merges(&[55, 89]));
+        assert!(!rule.merges(&[2, 2]));
+        assert!(!rule.merges(&[2, 5]));
+        assert!(!rule.merges(&[89, 144]));
+        assert_eq!(rule.max_tile(), 144);
+    }
+
+    #[test]
+    fn test_threes_start_with_one_and_two() {
+        let rule = MergeRule::Threes;
+        assert!(rule.merges(&[1, 2]));
+        assert!(rule.merges(&[2, 1]));
+        assert!(rule.merges(&[3, 3]));
+        assert!(rule.merges(&[384, 384]));
+        assert!(!rule.merges(&[1, 1]));
+        assert!(!rule.merges(&[2, 2]));
+        assert!(!rule.merges(&[3, 6]));
+        assert!(!rule.merges(&[768, 768]));
+        assert_eq!(rule.spawns(), (1, 2, 0.5));
+    }
+
+    #[test]
+    fn test_triples_merge_three_equal_tiles() {
+        let rule = MergeRule::Triples;
+        assert_eq!(rule.group(), 3);
+        assert!(rule.merges(&[1, 1, 1]));
+        assert!(rule.merges(&[27, 27, 27]));
+        assert!(!rule.merges(&[3, 3]));
+        assert!(!rule.merges(&[1, 1, 3]));
+        assert!(!rule.merges(&[59049, 59049, 59049]));
+        assert_eq!(rule.spawns(), (1, 3, 0.9));
+    }
+
+    #[test]
+    fn test_rungs_share_notation_and_colors() {
+        for rule in MergeRule::ALL {
+            for (rank, rung) in rule.ladder().into_iter().enumerate() {
+                let value = Value::Number(rung);
+                assert_eq!(rule.rank(&value), Some(rank));
+                assert_eq!(rule.parse(&rule.notation(&value)), Some(value));
+                assert_eq!(
+                    rule.classic(&value),
+                    Value::Number(MergeRule::Classic.ladder()[rank])
+                );
+            }
+            assert_eq!(rule.notation(&Value::Wall), "X");
+            assert_eq!(rule.parse("0"), Some(Value::Empty));
+            assert_eq!(rule.to_string().parse(), Ok(rule));
+        }
+        assert_eq!(
+            MergeRule::Fibonacci.notation(&Value::Number(144)),
+            "B".to_string()
+        );
+        assert_eq!(MergeRule::Threes.parse("3"), Some(Value::Number(3)));
+        assert!("doubling".parse::<MergeRule>().is_err());
+    }
+
+    #[test]
+    fn test_rules_cycle() {
+        let mut rule = MergeRule::Classic;
+        for expected in MergeRule::ALL.iter().skip(1) {
+            rule = rule.next();
+            assert_eq!(rule, *expected);
+        }
+        assert_eq!(rule.next(), MergeRule::Classic);
+    }
+}
@@ -0,0 +1,509 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::highscores::Entry;
+use crate::layout::Layout;
+use crate::menu::AppState;
+use crate::merge::MergeRule;
+use crate::theme::{ThemedText, Themes};
+use crate::tile::value::Value;
+use crate::{GameFinished, GameOutcome, GameState};
+
+pub const TIME_ATTACK_SECONDS: u32 = 180;
+pub const MOVE_LIMIT: u32 = 100;
+pub const RACE_TARGET: u32 = 512;
+
+/// What a game is played for. Modes other than `Classic` end on their own,
+/// and every mode has its own high score table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum GameMode {
+    /// Play until there are no moves left.
+    #[default]
+    Classic,
+    /// The highest score in `seconds`.
+    TimeAttack { seconds: u32 },
+    /// The highest tile within `moves` moves.
+    MoveLimit { moves: u32 },
+    /// Reach `target` as fast as possible.
+    TargetRace { target: u32 },
+    /// Make `target` within `moves` moves from an authored position.
+    Puzzle { target: u32, moves: u32 },
+    /// Classic play from a position built in the board editor.
+    Sandbox,
+}
+
+/// Why a game mode ended a game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModeEnd {
+    TimeUp,
+    OutOfMoves,
+    TargetReached,
+}
+
+/// Whether `max_tile` under `rule` is on the rung of the classic `target`
+/// or above, targets are always written as classic tiles.
+fn reaches(rule: MergeRule, max_tile: u32, target: u32) -> bool {
+    match rule.classic(&Value::Number(max_tile)) {
+        Value::Number(rung) => rung >= target,
+        Value::Empty | Value::Wall => false,
+    }
+}
+
+/// `m:ss` for `seconds`.
+pub fn clock(seconds: f32) -> String {
+    let seconds = seconds.max(0.0) as u32;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 4] = [
+        GameMode::Classic,
+        GameMode::TimeAttack {
+            seconds: TIME_ATTACK_SECONDS,
+        },
+        GameMode::MoveLimit { moves: MOVE_LIMIT },
+        GameMode::TargetRace {
+            target: RACE_TARGET,
+        },
+    ];
+
+    /// The next entry of `ALL`, modes with other limits from a hand edited
+    /// settings file continue after their kind.
+    pub fn next(&self) -> GameMode {
+        let index = GameMode::ALL
+            .iter()
+            .position(|mode| std::mem::discriminant(mode) == std::mem::discriminant(self))
+            .unwrap_or_default();
+        GameMode::ALL[(index + 1) % GameMode::ALL.len()]
+    }
+
+    /// Whether the game has a running clock.
+    pub fn is_timed(&self) -> bool {
+        matches!(
+            self,
+            GameMode::TimeAttack { .. } | GameMode::TargetRace { .. }
+        )
+    }
+
+    /// Whether a game under `rule` that made `moves` moves in `seconds` and
+    /// reached `max_tile` is over.
+    pub fn end(
+        &self,
+        moves: usize,
+        seconds: f32,
+        max_tile: u32,
+        rule: MergeRule,
+    ) -> Option<ModeEnd> {
+        match *self {
+            GameMode::Classic | GameMode::Sandbox => None,
+            GameMode::TimeAttack { seconds: limit } if seconds >= limit as f32 => {
+                Some(ModeEnd::TimeUp)
+            }
+            GameMode::MoveLimit { moves: limit } if moves >= limit as usize => {
+                Some(ModeEnd::OutOfMoves)
+            }
+            GameMode::TargetRace { target } if reaches(rule, max_tile, target) => {
+                Some(ModeEnd::TargetReached)
+            }
+            GameMode::Puzzle { target, .. } if reaches(rule, max_tile, target) => {
+                Some(ModeEnd::TargetReached)
+            }
+            GameMode::Puzzle { moves: limit, .. } if moves >= limit as usize => {
+                Some(ModeEnd::OutOfMoves)
+            }
+            _ => None,
+        }
+    }
+
+    /// What the HUD shows, nothing in classic games.
+    pub fn hud(&self, moves: usize, seconds: f32) -> String {
+        match *self {
+            GameMode::Classic => String::new(),
+            GameMode::Sandbox => "sandbox".to_string(),
+            GameMode::TimeAttack { seconds: limit } => {
+                format!("{} left", clock(limit as f32 - seconds))
+            }
+            GameMode::MoveLimit { moves: limit } => {
+                format!("{} moves left", (limit as usize).saturating_sub(moves))
+            }
+            GameMode::TargetRace { target } => format!("race to {} {}", target, clock(seconds)),
+            GameMode::Puzzle {
+                target,
+                moves: limit,
+            } => format!(
+                "make {} in {} moves",
+                target,
+                (limit as usize).saturating_sub(moves)
+            ),
+        }
+    }
+
+    /// Whether a finished game goes on the high score table at all. Puzzles
+    /// keep their own progress instead, edited positions are not ranked.
+    pub fn qualifies(&self, game: &GameFinished) -> bool {
+        match *self {
+            GameMode::TargetRace { target } => reaches(game.rule, game.max_tile, target),
+            GameMode::Puzzle { .. } | GameMode::Sandbox => false,
+            _ => game.score > 0,
+        }
+    }
+
+    /// How two entries of this mode's table compare, `Less` ranks first.
+    pub fn compare(&self, a: &Entry, b: &Entry) -> Ordering {
+        match self {
+            GameMode::Classic | GameMode::TimeAttack { .. } | GameMode::Sandbox => {
+                b.score.cmp(&a.score)
+            }
+            GameMode::MoveLimit { .. } => (b.max_tile, b.score).cmp(&(a.max_tile, a.score)),
+            GameMode::TargetRace { .. } => (a.millis, a.moves).cmp(&(b.millis, b.moves)),
+            GameMode::Puzzle { .. } => a.moves.cmp(&b.moves),
+        }
+    }
+
+    /// What an entry of this mode's table is ranked by.
+    pub fn result(&self, entry: &Entry) -> String {
+        match self {
+            GameMode::Classic | GameMode::TimeAttack { .. } | GameMode::Sandbox => {
+                format!("{} ({})", entry.score, entry.max_tile)
+            }
+            GameMode::MoveLimit { .. } => format!("{} ({})", entry.max_tile, entry.score),
+            GameMode::TargetRace { .. } => format!(
+                "{} ({} moves)",
+                clock(entry.millis as f32 / 1000.0),
+                entry.moves
+            ),
+            GameMode::Puzzle { .. } => format!("{} moves", entry.moves),
+        }
+    }
+
+    /// The name the menus show.
+    pub fn name(&self) -> String {
+        match self {
+            GameMode::Classic => "classic".to_string(),
+            GameMode::TimeAttack { seconds } => format!("time attack {}", clock(*seconds as f32)),
+            GameMode::MoveLimit { moves } => format!("{} moves", moves),
+            GameMode::TargetRace { target } => format!("race to {}", target),
+            GameMode::Puzzle { target, moves } => format!("{} in {} moves", target, moves),
+            GameMode::Sandbox => "sandbox".to_string(),
+        }
+    }
+}
+
+/// The rule set names of high score tables, e.g. `time-attack:180` or
+/// `puzzle:256:5`.
+impl fmt::Display for GameMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameMode::Classic => write!(f, "classic"),
+            GameMode::TimeAttack { seconds } => write!(f, "time-attack:{}", seconds),
+            GameMode::MoveLimit { moves } => write!(f, "move-limit:{}", moves),
+            GameMode::TargetRace { target } => write!(f, "target-race:{}", target),
+            GameMode::Puzzle { target, moves } => write!(f, "puzzle:{}:{}", target, moves),
+            GameMode::Sandbox => write!(f, "sandbox"),
+        }
+    }
+}
+
+impl FromStr for GameMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("unknown game mode {}", s);
+        match s {
+            "classic" => return Ok(GameMode::Classic),
+            "sandbox" => return Ok(GameMode::Sandbox),
+            _ => {}
+        }
+        let (kind, limit) = s.split_once(':').ok_or_else(invalid)?;
+        if kind == "puzzle" {
+            let (target, moves) = limit.split_once(':').ok_or_else(invalid)?;
+            return Ok(GameMode::Puzzle {
+                target: target.parse().map_err(|_| invalid())?,
+                moves: moves.parse().map_err(|_| invalid())?,
+            });
+        }
+        let limit = limit.parse().map_err(|_| invalid())?;
+        match kind {
+            "time-attack" => Ok(GameMode::TimeAttack { seconds: limit }),
+            "move-limit" => Ok(GameMode::MoveLimit { moves: limit }),
+            "target-race" => Ok(GameMode::TargetRace { target: limit }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Timer or moves left of the running game, in the top left corner.
+#[derive(Component)]
+pub struct ModeHud;
+
+fn setup_mode_hud(mut commands: Commands, themes: Res<Themes>) {
+    let hud = TextBundle::from_section(
+        "",
+        TextStyle {
+            font: themes.font.clone(),
+            font_size: 30.0,
+            color: themes.current().text,
+        },
+    )
+    .with_style(Style {
+        position_type: PositionType::Absolute,
+        top: Val::Px(12.0),
+        left: Val::Px(12.0),
+        ..Default::default()
+    });
+    commands.spawn(hud).insert(ModeHud).insert(ThemedText);
+}
+
+/// The clock starts with the first move and stops when the game ends.
+fn tick_clock(time: Res<Time>, mut game: ResMut<GameState>) {
+    if game.mode.is_timed() && game.ended.is_none() && !game.finished && !game.moves.is_empty() {
+        game.seconds += time.delta_seconds();
+    }
+}
+
+/// Ends the game once its mode says so, after every turn and while the
+/// clock runs.
+fn end_games(
+    mut game: ResMut<GameState>,
+    mut outcomes: EventWriter<GameOutcome>,
+    mut finished: EventWriter<GameFinished>,
+) {
+    if game.watching || game.finished || game.ended.is_some() {
+        return;
+    }
+    let Some(end) = game.mode.end(
+        game.moves.len(),
+        game.seconds,
+        game.board.max_tile(),
+        game.board.rule,
+    ) else {
+        return;
+    };
+    game.ended = Some(end);
+    // moves typed ahead are not played once the game is over
+    game.queued_moves.clear();
+    outcomes.send(GameOutcome::Ended(end));
+    if let Some(game) = game.finish() {
+        finished.send(game);
+    }
+}
+
+fn update_mode_hud(
+    game: Option<Res<GameState>>,
+    layout: Res<Layout>,
+    mut huds: Query<&mut Text, With<ModeHud>>,
+) {
+    let value = game.map_or_else(String::new, |game| {
+        game.mode.hud(game.moves.len(), game.seconds)
+    });
+    for mut text in huds.iter_mut() {
+        let section = &mut text.sections[0];
+        if section.value != value || section.style.font_size != layout.score_font {
+            section.value = value.clone();
+            section.style.font_size = layout.score_font;
+        }
+    }
+}
+
+pub struct ModePlugin;
+
+impl Plugin for ModePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_mode_hud)
+            .add_systems(
+                Update,
+                tick_clock
+                    .run_if(in_state(AppState::Playing).and_then(resource_exists::<GameState>)),
+            )
+            .add_systems(
+                PostUpdate,
+                end_games
+                    .after(crate::detect_outcome)
+                    .run_if(resource_exists::<GameState>),
+            )
+            .add_systems(Last, update_mode_hud);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::date::Date;
+
+    fn entry(mode: GameMode, score: u32, max_tile: u32, moves: usize, millis: u64) -> Entry {
+        Entry {
+            name: "ana".to_string(),
+            score,
+            max_tile,
+            moves,
+            millis,
+            date: Date::from_days(0),
+            seed: 1,
+            board_size: 4,
+            rules: mode.to_string(),
+            replay: String::new(),
+            checksum: 0,
+        }
+    }
+
+    #[test]
+    fn test_end_conditions() {
+        let classic = GameMode::Classic;
+        assert_eq!(
+            classic.end(10_000, 10_000.0, 2048, MergeRule::Classic),
+            None
+        );
+        assert_eq!(
+            GameMode::Sandbox.end(10_000, 10_000.0, 2048, MergeRule::Classic),
+            None
+        );
+
+        let time = GameMode::TimeAttack { seconds: 60 };
+        assert_eq!(time.end(100, 59.9, 512, MergeRule::Classic), None);
+        assert_eq!(
+            time.end(100, 60.0, 512, MergeRule::Classic),
+            Some(ModeEnd::TimeUp)
+        );
+
+        let moves = GameMode::MoveLimit { moves: 50 };
+        assert_eq!(moves.end(49, 1000.0, 512, MergeRule::Classic), None);
+        assert_eq!(
+            moves.end(50, 0.0, 8, MergeRule::Classic),
+            Some(ModeEnd::OutOfMoves)
+        );
+
+        let race = GameMode::TargetRace { target: 256 };
+        assert_eq!(race.end(500, 500.0, 128, MergeRule::Classic), None);
+        assert_eq!(
+            race.end(1, 1.0, 256, MergeRule::Classic),
+            Some(ModeEnd::TargetReached)
+        );
+
+        let puzzle = GameMode::Puzzle {
+            target: 64,
+            moves: 5,
+        };
+        assert_eq!(puzzle.end(4, 0.0, 32, MergeRule::Classic), None);
+        assert_eq!(
+            puzzle.end(5, 0.0, 32, MergeRule::Classic),
+            Some(ModeEnd::OutOfMoves)
+        );
+        // reaching the target with the last move solves it
+        assert_eq!(
+            puzzle.end(5, 0.0, 64, MergeRule::Classic),
+            Some(ModeEnd::TargetReached)
+        );
+    }
+
+    #[test]
+    fn test_targets_are_rungs_of_every_rule() {
+        let race = GameMode::TargetRace { target: 512 };
+        let puzzle = GameMode::Puzzle {
+            target: 64,
+            moves: 5,
+        };
+        for rule in MergeRule::ALL {
+            // 512 and 64 are the 9th and 6th rung of the classic ladder
+            let ladder = rule.ladder();
+            assert_eq!(race.end(1, 1.0, ladder[7], rule), None, "{}", rule);
+            assert_eq!(
+                race.end(1, 1.0, ladder[8], rule),
+                Some(ModeEnd::TargetReached),
+                "{}",
+                rule
+            );
+            assert_eq!(puzzle.end(1, 0.0, ladder[4], rule), None, "{}", rule);
+            assert_eq!(
+                puzzle.end(1, 0.0, ladder[5], rule),
+                Some(ModeEnd::TargetReached),
+                "{}",
+                rule
+            );
+
+            let finished = |max_tile| GameFinished {
+                seed: 1,
+                board_size: 4,
+                wrap: false,
+                rule,
+                score: 100,
+                max_tile,
+                moves: Vec::new(),
+                daily: None,
+                mode: race,
+                millis: 0,
+            };
+            assert!(!race.qualifies(&finished(ladder[7])), "{}", rule);
+            assert!(race.qualifies(&finished(ladder[8])), "{}", rule);
+        }
+        // the raw value says nothing, 729 is a 128 and 89 is a 1024
+        assert_eq!(race.end(1, 1.0, 729, MergeRule::Triples), None);
+        assert_eq!(
+            race.end(1, 1.0, 89, MergeRule::Fibonacci),
+            Some(ModeEnd::TargetReached)
+        );
+    }
+
+    #[test]
+    fn test_hud() {
+        assert_eq!(GameMode::Classic.hud(3, 4.0), "");
+        let time = GameMode::TimeAttack { seconds: 180 };
+        assert_eq!(time.hud(0, 0.0), "3:00 left");
+        assert_eq!(time.hud(0, 119.5), "1:00 left");
+        assert_eq!(time.hud(0, 200.0), "0:00 left");
+        let moves = GameMode::MoveLimit { moves: 100 };
+        assert_eq!(moves.hud(40, 0.0), "60 moves left");
+        let race = GameMode::TargetRace { target: 512 };
+        assert_eq!(race.hud(3, 65.0), "race to 512 1:05");
+        let puzzle = GameMode::Puzzle {
+            target: 256,
+            moves: 5,
+        };
+        assert_eq!(puzzle.hud(2, 9.0), "make 256 in 3 moves");
+    }
+
+    #[test]
+    fn test_modes_rank_differently() {
+        let time = GameMode::TimeAttack { seconds: 60 };
+        let high_score = entry(time, 900, 64, 80, 0);
+        let high_tile = entry(time, 800, 128, 80, 0);
+        assert_eq!(time.compare(&high_score, &high_tile), Ordering::Less);
+
+        let moves = GameMode::MoveLimit { moves: 80 };
+        assert_eq!(moves.compare(&high_score, &high_tile), Ordering::Greater);
+
+        let race = GameMode::TargetRace { target: 128 };
+        let fast = entry(race, 800, 128, 120, 40_000);
+        let slow = entry(race, 900, 128, 100, 50_000);
+        assert_eq!(race.compare(&fast, &slow), Ordering::Less);
+        assert_eq!(race.result(&fast), "0:40 (120 moves)");
+    }
+
+    #[test]
+    fn test_names_round_trip_and_cycle() {
+        for mode in GameMode::ALL {
+            assert_eq!(mode.to_string().parse::<GameMode>(), Ok(mode));
+        }
+        let puzzle = GameMode::Puzzle {
+            target: 256,
+            moves: 5,
+        };
+        assert_eq!("puzzle:256:5".parse::<GameMode>(), Ok(puzzle));
+        assert_eq!(puzzle.to_string(), "puzzle:256:5");
+        assert!("puzzle:256".parse::<GameMode>().is_err());
+        assert_eq!("sandbox".parse::<GameMode>(), Ok(GameMode::Sandbox));
+        assert!("time-attack".parse::<GameMode>().is_err());
+        assert!("zen:3".parse::<GameMode>().is_err());
+
+        let mut mode = GameMode::Classic;
+        for _ in 0..GameMode::ALL.len() {
+            mode = mode.next();
+        }
+        assert_eq!(mode, GameMode::Classic);
+        // other limits keep their place in the cycle
+        assert_eq!(GameMode::MoveLimit { moves: 7 }.next(), GameMode::ALL[3]);
+    }
+}
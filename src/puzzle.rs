@@ -0,0 +1,418 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::board::Board;
+use crate::direction::Direction;
+use crate::mode::{GameMode, ModeEnd};
+use crate::settings::{settings_path, write_atomically};
+use crate::spawner::{Spawn, Spawner};
+use crate::{GameOutcome, GameState};
+
+/// Lives next to the settings file.
+pub const PUZZLE_PROGRESS_FILE: &str = "puzzle_progress.ron";
+
+/// The puzzles that ship with the game, in the order they unlock.
+const SHIPPED_PUZZLES: &str = include_str!("../assets/puzzles.ron");
+
+/// Where the tiles after every move come from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Spawns {
+    /// The same random tiles every time, drawn from an rng seeded with this.
+    Seeded(u64),
+    /// Exactly these tiles, one per move until they run out.
+    Script(Vec<Spawn>),
+}
+
+/// Make a `tile` within `moves` moves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Goal {
+    pub tile: u32,
+    pub moves: u32,
+}
+
+/// An authored starting position with a goal. `board` is in board
+/// notation, e.g. `"3301100000000010"`, and its length sets the board size.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Puzzle {
+    pub name: String,
+    pub board: String,
+    pub spawns: Spawns,
+    pub goal: Goal,
+    /// The move count to beat, `goal.moves` is only the limit.
+    pub par: u32,
+}
+
+impl Puzzle {
+    /// The starting position. Puzzles are checked when they are loaded.
+    pub fn board(&self) -> Board {
+        self.board.parse().expect("puzzle board")
+    }
+
+    pub fn spawner(&self) -> Spawner {
+        match &self.spawns {
+            Spawns::Seeded(seed) => Spawner::seeded(*seed),
+            Spawns::Script(spawns) => Spawner::script(spawns),
+        }
+    }
+
+    pub fn mode(&self) -> GameMode {
+        GameMode::Puzzle {
+            target: self.goal.tile,
+            moves: self.goal.moves,
+        }
+    }
+
+    fn check(&self) -> Result<(), String> {
+        let board: Board = self
+            .board
+            .parse()
+            .map_err(|_| format!("puzzle {} has an invalid board", self.name))?;
+        if let Spawns::Script(spawns) = &self.spawns {
+            let invalid = spawns.iter().any(|spawn| {
+                spawn.row >= board.size
+                    || spawn.col >= board.size
+                    || spawn.value(board.rule).is_none()
+            });
+            if invalid {
+                return Err(format!("puzzle {} has an invalid spawn", self.name));
+            }
+        }
+        if self.par > self.goal.moves {
+            return Err(format!("puzzle {} has a par above its limit", self.name));
+        }
+        Ok(())
+    }
+}
+
+/// The shortest way to reach the goal of `puzzle`, `None` when there is
+/// none within its move limit.
+pub fn solve(puzzle: &Puzzle) -> Option<Vec<Direction>> {
+    let mut level = vec![(puzzle.board(), puzzle.spawner(), vec![])];
+    for _ in 0..=puzzle.goal.moves {
+        let mut next: Vec<(Board, Spawner, Vec<Direction>)> = vec![];
+        // different moves often lead to the same position
+        let mut seen: HashMap<String, Vec<Spawner>> = HashMap::new();
+        for (board, spawner, moves) in level {
+            if board.max_tile() >= puzzle.goal.tile {
+                return Some(moves);
+            }
+            for direction in [
+                Direction::Left,
+                Direction::Up,
+                Direction::Right,
+                Direction::Down,
+            ] {
+                let actions = board.plan_slide_and_merge(&direction);
+                if actions.is_empty() {
+                    continue;
+                }
+                let mut board = board.clone();
+                let mut spawner = spawner.clone();
+                for action in actions {
+                    board.apply(action);
+                }
+                if let Some(spawn) = spawner.plan(&board) {
+                    board.apply(spawn);
+                }
+                let spawners = seen.entry(board.to_string()).or_default();
+                if spawners.contains(&spawner) {
+                    continue;
+                }
+                spawners.push(spawner.clone());
+                let mut moves = moves.clone();
+                moves.push(direction);
+                next.push((board, spawner, moves));
+            }
+        }
+        level = next;
+    }
+    None
+}
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct Puzzles(pub Vec<Puzzle>);
+
+impl Puzzles {
+    pub fn parse(ron: &str) -> Result<Puzzles, String> {
+        let puzzles: Vec<Puzzle> = ron::from_str(ron).map_err(|err| err.to_string())?;
+        for puzzle in puzzles.iter() {
+            puzzle.check()?;
+        }
+        Ok(Puzzles(puzzles))
+    }
+
+    pub fn shipped() -> Puzzles {
+        Puzzles::parse(SHIPPED_PUZZLES).expect("shipped puzzles")
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Puzzle> {
+        self.0.get(index)
+    }
+}
+
+/// The fewest moves every solved puzzle took, by puzzle name.
+#[derive(Resource, Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PuzzleProgress {
+    pub solved: BTreeMap<String, u32>,
+}
+
+impl PuzzleProgress {
+    pub fn parse(ron: &str) -> Result<PuzzleProgress, String> {
+        ron::from_str(ron).map_err(|err| err.to_string())
+    }
+
+    pub fn to_ron(&self) -> String {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .expect("puzzle progress serialize")
+    }
+
+    /// Loads `path`, starting with nothing solved when there is no file yet
+    /// or it cannot be read.
+    pub fn load_or_default(path: &Path) -> PuzzleProgress {
+        match fs::read_to_string(path) {
+            Ok(ron) => PuzzleProgress::parse(&ron).unwrap_or_else(|err| {
+                warn!("malformed puzzle progress: {} ({})", err, path.display());
+                PuzzleProgress::default()
+            }),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => PuzzleProgress::default(),
+            Err(err) => {
+                warn!(
+                    "could not read puzzle progress: {} ({})",
+                    err,
+                    path.display()
+                );
+                PuzzleProgress::default()
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        write_atomically(path, &self.to_ron())
+    }
+
+    pub fn best(&self, puzzle: &Puzzle) -> Option<u32> {
+        self.solved.get(&puzzle.name).copied()
+    }
+
+    /// The first puzzle is always open, every other one once the puzzle
+    /// before it is solved.
+    pub fn is_unlocked(&self, puzzles: &Puzzles, index: usize) -> bool {
+        index == 0
+            || index
+                .checked_sub(1)
+                .and_then(|before| puzzles.get(before))
+                .is_some_and(|before| self.best(before).is_some())
+    }
+
+    /// Keeps `moves` when the puzzle was not solved in fewer before.
+    pub fn record(&mut self, puzzle: &Puzzle, moves: u32) -> bool {
+        if self.best(puzzle).is_some_and(|best| best <= moves) {
+            return false;
+        }
+        self.solved.insert(puzzle.name.clone(), moves);
+        true
+    }
+}
+
+/// Where the puzzle progress lives, `None` when settings are not persisted.
+pub fn puzzle_progress_path() -> Option<PathBuf> {
+    settings_path().map(|path| path.with_file_name(PUZZLE_PROGRESS_FILE))
+}
+
+#[derive(Resource, Debug)]
+pub struct PuzzleProgressPath(pub Option<PathBuf>);
+
+/// Remembers a puzzle as solved once its goal is reached.
+fn record_solves(
+    path: Res<PuzzleProgressPath>,
+    puzzles: Res<Puzzles>,
+    mut progress: ResMut<PuzzleProgress>,
+    game: Option<Res<GameState>>,
+    mut outcomes: EventReader<GameOutcome>,
+) {
+    let solved = outcomes
+        .read()
+        .any(|outcome| *outcome == GameOutcome::Ended(ModeEnd::TargetReached));
+    let Some(game) = game.filter(|_| solved) else {
+        return;
+    };
+    let Some(puzzle) = game.puzzle.and_then(|index| puzzles.get(index)) else {
+        return;
+    };
+    let moves = game.moves.len() as u32;
+    println!(
+        "solved {} in {} moves, par {}",
+        puzzle.name, moves, puzzle.par
+    );
+    if !progress.record(puzzle, moves) {
+        return;
+    }
+    if let Some(path) = &path.0 {
+        if let Err(err) = progress.save(path) {
+            warn!(
+                "could not write puzzle progress: {} ({})",
+                err,
+                path.display()
+            );
+        }
+    }
+}
+
+pub struct PuzzlePlugin;
+
+impl Plugin for PuzzlePlugin {
+    fn build(&self, app: &mut App) {
+        let path = puzzle_progress_path();
+        let progress = path
+            .as_deref()
+            .map(PuzzleProgress::load_or_default)
+            .unwrap_or_default();
+        app.insert_resource(Puzzles::shipped())
+            .insert_resource(progress)
+            .insert_resource(PuzzleProgressPath(path))
+            .add_systems(Last, record_solves);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay;
+
+    #[test]
+    fn test_shipped_puzzles_are_solvable() {
+        let puzzles = Puzzles::shipped();
+        assert!(!puzzles.0.is_empty());
+        for puzzle in puzzles.0.iter() {
+            let solution =
+                solve(puzzle).unwrap_or_else(|| panic!("{} has no solution", puzzle.name));
+            assert!(
+                solution.len() as u32 <= puzzle.par,
+                "{} is solved in {}, above par {}",
+                puzzle.name,
+                replay::encode(&solution),
+                puzzle.par
+            );
+        }
+    }
+
+    #[test]
+    fn test_stuck_puzzles_are_lost() {
+        use crate::action::Action;
+        use crate::consistency::ActionLog;
+        use crate::tile::{position::Position, value::Value, Tile};
+        use crate::{detect_outcome, GameFinished};
+        use std::collections::VecDeque;
+
+        // the script is empty, so no tile comes after the move that left the
+        // two tiles boxed in by walls
+        let puzzle = Puzzle {
+            name: "boxed in".to_string(),
+            board: "1XX2".to_string(),
+            spawns: Spawns::Script(vec![]),
+            goal: Goal { tile: 8, moves: 5 },
+            par: 1,
+        };
+        let game = |deferred_events| GameState {
+            board: puzzle.board(),
+            entities: Default::default(),
+            board_entity: Entity::from_raw(0),
+            deferred_events,
+            replay: vec![],
+            watching: false,
+            daily: None,
+            puzzle: Some(0),
+            queued_moves: VecDeque::new(),
+            history: VecDeque::new(),
+            moves: vec![Direction::Left],
+            score: 0,
+            won: false,
+            finished: false,
+            mode: puzzle.mode(),
+            seconds: 0.0,
+            ended: None,
+            spawner: puzzle.spawner(),
+            seed: 0,
+            log: ActionLog::default(),
+        };
+        let tile = Tile {
+            value: Value::Number(2),
+            position: Position { row: 0, col: 0 },
+        };
+        let outcomes = |game: GameState| {
+            let mut app = App::new();
+            app.add_event::<Action>()
+                .add_event::<GameOutcome>()
+                .add_event::<GameFinished>()
+                .insert_resource(game)
+                .add_systems(Update, detect_outcome);
+            app.world
+                .send_event(Action::SlideTile(tile, Position { row: 0, col: 0 }));
+            app.update();
+            let outcomes = app.world.resource::<Events<GameOutcome>>();
+            outcomes
+                .get_reader()
+                .read(outcomes)
+                .copied()
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(outcomes(game(vec![])), vec![GameOutcome::Lost]);
+        // a tile that is still to come decides once it is there
+        assert_eq!(outcomes(game(vec![Action::SpawnRandomTile(tile)])), vec![]);
+    }
+
+    #[test]
+    fn test_solver_finds_the_shortest_solution() {
+        let puzzle = Puzzle {
+            name: "test".to_string(),
+            board: "1100110000000000".to_string(),
+            spawns: Spawns::Script(vec![]),
+            goal: Goal { tile: 8, moves: 3 },
+            par: 2,
+        };
+        assert_eq!(solve(&puzzle).map(|moves| moves.len()), Some(2));
+        let too_far = Puzzle {
+            goal: Goal { tile: 16, moves: 3 },
+            ..puzzle
+        };
+        assert_eq!(solve(&too_far), None);
+    }
+
+    #[test]
+    fn test_invalid_puzzles_are_rejected() {
+        let puzzle = |board: &str, spawn: &str| {
+            format!(
+                "[(name: \"p\", board: \"{}\", spawns: Script([{}]), goal: (tile: 8, moves: 2), par: 2)]",
+                board, spawn
+            )
+        };
+        assert!(Puzzles::parse(&puzzle("1100", "")).is_ok());
+        assert!(Puzzles::parse(&puzzle("11000", "")).is_err());
+        assert!(Puzzles::parse(&puzzle("1100", "(row: 2, col: 0, value: '1')")).is_err());
+        assert!(Puzzles::parse(&puzzle("1100", "(row: 1, col: 0, value: '0')")).is_err());
+        // walls are part of the board, they never spawn
+        assert!(Puzzles::parse(&puzzle("1X00", "")).is_ok());
+        assert!(Puzzles::parse(&puzzle("1100", "(row: 1, col: 0, value: 'X')")).is_err());
+    }
+
+    #[test]
+    fn test_puzzles_unlock_in_order() {
+        let puzzles = Puzzles::shipped();
+        let mut progress = PuzzleProgress::default();
+        assert!(progress.is_unlocked(&puzzles, 0));
+        assert!(!progress.is_unlocked(&puzzles, 1));
+        assert!(progress.record(&puzzles.0[0], 3));
+        assert!(progress.is_unlocked(&puzzles, 1));
+        assert!(!progress.is_unlocked(&puzzles, 2));
+        // only better solutions replace the best one
+        assert!(!progress.record(&puzzles.0[0], 4));
+        assert!(progress.record(&puzzles.0[0], 1));
+        assert_eq!(progress.best(&puzzles.0[0]), Some(1));
+        assert_eq!(PuzzleProgress::parse(&progress.to_ron()), Ok(progress));
+    }
+}
@@ -0,0 +1,370 @@
+use std::collections::HashMap;
+
+use crate::action::Action;
+use crate::board::Board;
+use crate::direction::Direction;
+use crate::merge::MergeRule;
+use crate::tile::position::Position;
+use crate::tile::value::Value;
+
+/// A single tile movement as computed by the reference implementation.
+/// `sources` holds one position for a slide and two or three for a merge,
+/// ordered along the line traversal, so the first source is closer to the
+/// edge.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Move {
+    pub sources: Vec<(usize, usize)>,
+    pub to: (usize, usize),
+    pub value: u32,
+}
+
+fn key(pos: &Position) -> (usize, usize) {
+    (pos.row, pos.col)
+}
+
+fn number(value: &Value) -> u32 {
+    match value {
+        Value::Empty | Value::Wall => 0,
+        Value::Number(n) => *n,
+    }
+}
+
+/// How many tiles merge at once under `rule`. Written out here instead of
+/// asking `MergeRule`, so a mistake there shows up as a difference.
+fn group(rule: MergeRule) -> usize {
+    match rule {
+        MergeRule::Triples => 3,
+        MergeRule::Classic | MergeRule::Fibonacci | MergeRule::Threes => 2,
+    }
+}
+
+/// Whether `values` merge under `rule`, again independent of `MergeRule`.
+fn merges(rule: MergeRule, values: &[u32]) -> bool {
+    let power_of = |base: u32, mut n: u32| {
+        while n > 1 && n.is_multiple_of(base) {
+            n /= base;
+        }
+        n == 1
+    };
+    match (rule, values) {
+        (MergeRule::Classic, [a, b]) => a == b && *a >= 2 && power_of(2, *a) && *a <= 1024,
+        (MergeRule::Fibonacci, [a, b]) => {
+            // walks the sequence 1, 1, 2, 3, 5, ... up to 144
+            let (mut x, mut y) = (1, 1);
+            while y <= 144 {
+                if (*a, *b) == (x, y) || (*a, *b) == (y, x) {
+                    return x + y <= 144;
+                }
+                (x, y) = (y, x + y);
+            }
+            false
+        }
+        (MergeRule::Threes, [a, b]) => {
+            matches!((a, b), (1, 2) | (2, 1))
+                || (a == b && a.is_multiple_of(3) && power_of(2, *a / 3) && *a <= 384)
+        }
+        (MergeRule::Triples, [a, b, c]) => a == b && b == c && power_of(3, *a) && *a <= 19683,
+        _ => false,
+    }
+}
+
+/// Compresses a line by first dropping all empty cells and then merging
+/// neighbours from the front, as many at a time as the board's rule merges.
+/// Every tile lands on the next free slot, so nothing here depends on
+/// deferred state.
+pub fn move_line(board: &Board, line: &[Position]) -> Vec<Move> {
+    let tiles: Vec<(Position, u32)> = line
+        .iter()
+        .map(|pos| (*pos, number(&board.get_value(pos))))
+        .filter(|(_, value)| *value != 0)
+        .collect();
+
+    let group = group(board.rule);
+    let mut moves = vec![];
+    let mut i = 0;
+    while i < tiles.len() {
+        let to = key(&line[moves.len()]);
+        let next = &tiles[i..(i + group).min(tiles.len())];
+        let values: Vec<u32> = next.iter().map(|(_, value)| *value).collect();
+        if merges(board.rule, &values) {
+            moves.push(Move {
+                sources: next.iter().map(|(pos, _)| key(pos)).collect(),
+                to,
+                value: values.iter().sum(),
+            });
+            i += group;
+        } else {
+            let (pos, value) = tiles[i];
+            moves.push(Move {
+                sources: vec![key(&pos)],
+                to,
+                value,
+            });
+            i += 1;
+        }
+    }
+    moves
+}
+
+/// Computes the board after moving in `direction` together with all tile
+/// movements. Tiles that stay in place are reported as well. Walls stay and
+/// the cells between them are moved like lines of their own.
+pub fn move_board(board: &Board, direction: &Direction) -> (Board, Vec<Move>) {
+    let mut result = board.clone();
+    let mut moves = vec![];
+    let lines = board.traversal_map.get(direction).unwrap().iter();
+    for line in lines.flat_map(|line| line.split(|pos| board.get_value(pos) == Value::Wall)) {
+        let line_moves = move_line(board, line);
+        for pos in line {
+            result.set_value(pos, Value::Empty);
+        }
+        for m in line_moves.iter() {
+            let to = Position {
+                row: m.to.0,
+                col: m.to.1,
+            };
+            result.set_value(&to, Value::Number(m.value));
+        }
+        moves.extend(line_moves);
+    }
+    (result, moves)
+}
+
+/// Translates planned actions into reference moves so both can be compared.
+pub fn moves_from_actions(actions: &[Action]) -> Vec<Move> {
+    actions
+        .iter()
+        .map(|action| match action {
+            Action::SpawnRandomTile(tile) => panic!("unexpected spawn {:?}", tile),
+            Action::SlideTile(tile, to) => Move {
+                sources: vec![key(&tile.position)],
+                to: key(to),
+                value: number(&tile.value),
+            },
+            Action::MergeTiles(tile1, tile2, to, value) => Move {
+                sources: vec![key(&tile1.position), key(&tile2.position)],
+                to: key(to),
+                value: number(value),
+            },
+            Action::MergeThreeTiles(tile1, tile2, tile3, to, value) => Move {
+                sources: vec![
+                    key(&tile1.position),
+                    key(&tile2.position),
+                    key(&tile3.position),
+                ],
+                to: key(to),
+                value: number(value),
+            },
+        })
+        .collect()
+}
+
+/// Replays `actions` one after another and checks that every action only
+/// refers to tiles that are still where it claims they are and that merges
+/// follow the board's rule. The former is the property the renderer relies
+/// on when it maps positions to entities.
+pub fn check_action_sequence(board: &Board, actions: &[Action]) -> Result<Board, String> {
+    let mut board = board.clone();
+    let mut moved: HashMap<(usize, usize), usize> = HashMap::new();
+    for (i, action) in actions.iter().enumerate() {
+        let (tiles, to) = match action {
+            Action::SpawnRandomTile(tile) => {
+                return Err(format!("#{} unexpected spawn {:?}", i, tile))
+            }
+            Action::SlideTile(tile, to) => (vec![*tile], *to),
+            Action::MergeTiles(tile1, tile2, to, _) => (vec![*tile1, *tile2], *to),
+            Action::MergeThreeTiles(tile1, tile2, tile3, to, _) => {
+                (vec![*tile1, *tile2, *tile3], *to)
+            }
+        };
+        if let Some((_, value)) = action.merged() {
+            let values: Vec<u32> = tiles.iter().map(|tile| number(&tile.value)).collect();
+            let sum = tiles
+                .iter()
+                .fold(Value::Empty, |sum, tile| sum.merge(tile.value));
+            if !merges(board.rule, &values) || sum != value {
+                return Err(format!("#{} invalid merge {:?}", i, action));
+            }
+        }
+        for tile in tiles.iter() {
+            if board.get_value(&tile.position) != tile.value {
+                return Err(format!(
+                    "#{} {:?} expects {} at {} but found {}",
+                    i,
+                    action,
+                    tile.value,
+                    tile.position,
+                    board.get_value(&tile.position)
+                ));
+            }
+            if let Some(j) = moved.insert(key(&tile.position), i) {
+                return Err(format!(
+                    "#{} {:?} moves a tile already moved by #{}",
+                    i, action, j
+                ));
+            }
+        }
+        let target_is_source = tiles.iter().any(|tile| tile.position == to);
+        if !target_is_source && board.get_value(&to) != Value::Empty {
+            return Err(format!("#{} {:?} targets occupied {}", i, action, to));
+        }
+        board.apply(action.clone());
+    }
+    Ok(board)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::merge::MergeRule;
+
+    const DIRECTIONS: [Direction; 4] = [
+        Direction::Left,
+        Direction::Right,
+        Direction::Up,
+        Direction::Down,
+    ];
+    const EXPONENTS: &str = "0123456789AB";
+    // every equality pattern of four cells plus the merge cap at the top of
+    // the ladder and walls, the low rungs cover the neighbours other rules
+    // merge
+    const LINE_ALPHABET: &str = "01234ABX";
+
+    fn compare(board: &Board, direction: &Direction) {
+        let actions = board.plan_slide_and_merge(direction);
+        let (expected_board, expected_moves) = move_board(board, direction);
+        let context = format!("{} --{}-->", board, direction);
+
+        let actual_board = check_action_sequence(board, &actions)
+            .unwrap_or_else(|err| panic!("{} {}", context, err));
+        assert_eq!(
+            actual_board.to_string(),
+            expected_board.to_string(),
+            "{} final board",
+            context
+        );
+
+        let mut actual: Vec<Move> = moves_from_actions(&actions);
+        let mut expected: Vec<Move> = expected_moves
+            .into_iter()
+            .filter(|m| m.sources != vec![m.to])
+            .collect();
+        actual.sort();
+        expected.sort();
+        assert_eq!(actual, expected, "{} moves {:?}", context, actions);
+    }
+
+    #[test]
+    fn test_reference_move_line() {
+        let board: Board = "1122000000000000".parse().unwrap();
+        let (board, moves) = move_board(&board, &Direction::Left);
+        assert_eq!(board.to_string(), "2300000000000000");
+        assert_eq!(moves.len(), 2);
+        assert_eq!(moves[0].sources, vec![(0, 0), (0, 1)]);
+        assert_eq!(moves[1].sources, vec![(0, 2), (0, 3)]);
+        assert_eq!(moves[1].to, (0, 1));
+    }
+
+    #[test]
+    fn test_reference_merge_rules() {
+        let cases: [(MergeRule, &[u32], bool); 16] = [
+            (MergeRule::Classic, &[2, 2], true),
+            (MergeRule::Classic, &[1024, 1024], true),
+            (MergeRule::Classic, &[2048, 2048], false),
+            (MergeRule::Classic, &[6, 6], false),
+            (MergeRule::Fibonacci, &[1, 1], true),
+            (MergeRule::Fibonacci, &[3, 2], true),
+            (MergeRule::Fibonacci, &[55, 89], true),
+            (MergeRule::Fibonacci, &[89, 144], false),
+            (MergeRule::Fibonacci, &[2, 5], false),
+            (MergeRule::Threes, &[2, 1], true),
+            (MergeRule::Threes, &[384, 384], true),
+            (MergeRule::Threes, &[1, 1], false),
+            (MergeRule::Threes, &[9, 9], false),
+            (MergeRule::Triples, &[27, 27, 27], true),
+            (MergeRule::Triples, &[59049, 59049, 59049], false),
+            (MergeRule::Triples, &[3, 3], false),
+        ];
+        for (rule, values, expected) in cases {
+            assert_eq!(merges(rule, values), expected, "{} {:?}", rule, values);
+        }
+    }
+
+    #[test]
+    fn test_exhaustive_lines_against_reference() {
+        for (rule, direction) in MergeRule::ALL
+            .iter()
+            .flat_map(|rule| DIRECTIONS.iter().map(move |direction| (*rule, direction)))
+        {
+            for a in LINE_ALPHABET.chars() {
+                for b in LINE_ALPHABET.chars() {
+                    for c in LINE_ALPHABET.chars() {
+                        for d in LINE_ALPHABET.chars() {
+                            let line: String = [a, b, c, d].iter().collect();
+                            let s = match direction {
+                                Direction::Left | Direction::Right => {
+                                    format!("{}000000000000", line)
+                                }
+                                Direction::Up | Direction::Down => {
+                                    line.chars().map(|ch| format!("{}000", ch)).collect()
+                                }
+                            };
+                            let board = Board::parse_with(&s, rule).unwrap();
+                            compare(&board, direction);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_random_boards_against_reference() {
+        let mut rng = ChaCha8Rng::seed_from_u64(2048);
+        for _ in 0..2000 {
+            let s: String = (0..16)
+                .map(|_| {
+                    if rng.gen_bool(0.4) {
+                        '0'
+                    } else if rng.gen_bool(0.1) {
+                        'X'
+                    } else {
+                        EXPONENTS.as_bytes()[rng.gen_range(1..5)] as char
+                    }
+                })
+                .collect();
+            let board: Board = s.parse().unwrap();
+            for direction in DIRECTIONS.iter() {
+                compare(&board, direction);
+            }
+        }
+    }
+
+    #[test]
+    fn test_random_games_against_reference() {
+        for (rule, seed) in MergeRule::ALL
+            .iter()
+            .flat_map(|rule| (0..50).map(move |seed| (*rule, seed)))
+        {
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            let mut board = Board::new(4).merging(rule);
+            for _ in 0..2 {
+                let spawn = board.plan_spawn_random_tile(&mut rng).unwrap();
+                board.apply(spawn);
+            }
+            for _ in 0..500 {
+                let direction = DIRECTIONS[rng.gen_range(0..4)];
+                compare(&board, &direction);
+                if board.slide_and_merge(direction) {
+                    match board.plan_spawn_random_tile(&mut rng) {
+                        Some(spawn) => board.apply(spawn),
+                        None => break,
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use bevy::prelude::*;
+
+use crate::animation::AnimationConfig;
+use crate::board::Board;
+use crate::direction::Direction;
+use crate::menu::AppState;
+use crate::merge::MergeRule;
+use crate::spawner::Spawner;
+use crate::{play_queued_move, start_new_game, GameState};
+
+/// Seconds between replayed moves at normal animation speed.
+const REPLAY_INTERVAL: f32 = 0.3;
+
+/// A game to watch instead of play.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replay {
+    pub board_size: usize,
+    pub wrap: bool,
+    pub rule: MergeRule,
+    pub moves: Vec<Direction>,
+}
+
+/// What a replay ends in.
+#[derive(Debug, Clone)]
+pub struct ReplayResult {
+    pub board: Board,
+    pub score: u32,
+    pub max_tile: u32,
+}
+
+/// Moves as one letter each, e.g. `LLUR`.
+pub fn encode(moves: &[Direction]) -> String {
+    moves
+        .iter()
+        .map(|direction| direction.to_string())
+        .collect()
+}
+
+pub fn decode(moves: &str) -> Option<Vec<Direction>> {
+    moves
+        .chars()
+        .map(|letter| match letter {
+            'L' => Some(Direction::Left),
+            'R' => Some(Direction::Right),
+            'U' => Some(Direction::Up),
+            'D' => Some(Direction::Down),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Like `encode` but runs of the same move are counted, e.g. `3LU2R`.
+pub fn pack(moves: &[Direction]) -> String {
+    let mut packed = String::new();
+    let mut rest = moves;
+    while let Some(first) = rest.first() {
+        let run = rest.iter().take_while(|other| *other == first).count();
+        if run > 1 {
+            packed.push_str(&run.to_string());
+        }
+        packed.push_str(&first.to_string());
+        rest = &rest[run..];
+    }
+    packed
+}
+
+pub fn unpack(packed: &str) -> Option<Vec<Direction>> {
+    let mut moves = vec![];
+    let mut run = String::new();
+    for letter in packed.chars() {
+        if letter.is_ascii_digit() {
+            run.push(letter);
+            continue;
+        }
+        let count = if run.is_empty() { 1 } else { run.parse().ok()? };
+        let direction = decode(&letter.to_string())?;
+        moves.extend(std::iter::repeat_n(direction[0], count));
+        run.clear();
+    }
+    run.is_empty().then_some(moves)
+}
+
+/// Plays `moves` on a new `board_size` board the way the game does: two
+/// tiles to start with and one more after every move, all drawn from the
+/// rng seeded with `seed`. Games only record moves that changed the board,
+/// so a move that does not makes the replay invalid and gives `None`.
+pub fn simulate(board_size: usize, seed: u64, moves: &[Direction]) -> Option<ReplayResult> {
+    simulate_on(Board::new(board_size), seed, moves)
+}
+
+/// `simulate` on `board`, e.g. an empty wrapping one or one with another
+/// merge rule.
+pub fn simulate_on(mut board: Board, seed: u64, moves: &[Direction]) -> Option<ReplayResult> {
+    let mut spawner = Spawner::seeded(seed);
+    let mut score = 0;
+    for _ in 0..2 {
+        let spawn = spawner.plan(&board)?;
+        board.apply(spawn);
+    }
+    for direction in moves {
+        let actions = board.plan_slide_and_merge(direction);
+        if actions.is_empty() {
+            return None;
+        }
+        for action in actions {
+            score += action.score();
+            board.apply(action);
+        }
+        if let Some(spawn) = spawner.plan(&board) {
+            board.apply(spawn);
+        }
+    }
+    let max_tile = board.max_tile();
+    Some(ReplayResult {
+        board,
+        score,
+        max_tile,
+    })
+}
+
+/// Plays the moves of a replay one after another. Replay steps still play
+/// the next one right away.
+fn autoplay(
+    time: Res<Time>,
+    config: Res<AnimationConfig>,
+    mut game: ResMut<GameState>,
+    mut elapsed: Local<f32>,
+) {
+    if !game.watching || game.replay.is_empty() {
+        *elapsed = 0.0;
+        return;
+    }
+    *elapsed += time.delta_seconds() * config.speed;
+    if *elapsed < REPLAY_INTERVAL || !game.queued_moves.is_empty() {
+        return;
+    }
+    *elapsed = 0.0;
+    if let Some(direction) = game.replay.pop() {
+        game.queue_move(direction);
+    }
+}
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PreUpdate,
+            autoplay
+                .after(start_new_game)
+                .before(play_queued_move)
+                .run_if(in_state(AppState::Playing).and_then(resource_exists::<GameState>)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encoding_round_trip() {
+        let moves = vec![
+            Direction::Left,
+            Direction::Up,
+            Direction::Right,
+            Direction::Down,
+        ];
+        assert_eq!(encode(&moves), "LURD");
+        assert_eq!(decode("LURD"), Some(moves));
+        assert_eq!(decode("LUX"), None);
+    }
+
+    #[test]
+    fn test_packing_round_trip() {
+        let moves = decode("LLLURRDDDDDDDDDDDDL").unwrap();
+        assert_eq!(pack(&moves), "3LU2R12DL");
+        assert_eq!(unpack("3LU2R12DL"), Some(moves));
+        assert_eq!(unpack(""), Some(vec![]));
+        assert_eq!(unpack("3"), None);
+        assert_eq!(unpack("2X"), None);
+    }
+
+    #[test]
+    fn test_simulation_is_deterministic() {
+        let start = simulate(4, 5, &[]).unwrap();
+        assert_eq!(
+            start
+                .board
+                .tiles
+                .values()
+                .filter(|v| v.to_exponent() > 0)
+                .count(),
+            2
+        );
+
+        // play the first move that works a few times over
+        let mut moves = vec![];
+        for _ in 0..20 {
+            let board = simulate(4, 5, &moves).unwrap().board;
+            match board.hint() {
+                Some(direction) => moves.push(direction),
+                None => break,
+            }
+        }
+        let a = simulate(4, 5, &moves).unwrap();
+        let b = simulate(4, 5, &moves).unwrap();
+        assert_eq!(a.board.to_string(), b.board.to_string());
+        assert_eq!(a.score, b.score);
+        assert!(a.max_tile >= 4);
+    }
+
+    #[test]
+    fn test_moves_that_change_nothing_are_invalid() {
+        let board = simulate(4, 1, &[]).unwrap().board;
+        let stuck = [
+            Direction::Left,
+            Direction::Right,
+            Direction::Up,
+            Direction::Down,
+        ]
+        .into_iter()
+        .find(|direction| board.plan_slide_and_merge(direction).is_empty());
+        if let Some(direction) = stuck {
+            assert!(simulate(4, 1, &[direction]).is_none());
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use bevy::{prelude::*, sprite::Anchor};
+
+use crate::action::Action;
+use crate::animation::Easing;
+use crate::theme::{ThemedText, Themes};
+use crate::{to_screen, GameState, NewGame};
+
+const SCORE_COUNT_DURATION: f32 = 0.3;
+const POPUP_DURATION: f32 = 0.6;
+const POPUP_RISE: f32 = 60.0;
+
+/// Counts from `from` to `to` while `t` runs from 0 to 1.
+pub fn count(from: u32, to: u32, t: f32) -> u32 {
+    let t = t.clamp(0.0, 1.0);
+    (from as f32 + (to as f32 - from as f32) * t).round() as u32
+}
+
+/// The score that is currently shown, which trails the real score in
+/// `GameState` and catches up over `SCORE_COUNT_DURATION`.
+#[derive(Resource, Debug)]
+pub struct ScoreDisplay {
+    from: u32,
+    target: u32,
+    shown: u32,
+    timer: Timer,
+}
+
+impl Default for ScoreDisplay {
+    fn default() -> Self {
+        ScoreDisplay {
+            from: 0,
+            target: 0,
+            shown: 0,
+            timer: Timer::from_seconds(SCORE_COUNT_DURATION, TimerMode::Once),
+        }
+    }
+}
+
+impl ScoreDisplay {
+    fn retarget(&mut self, target: u32) {
+        self.from = self.shown;
+        self.target = target;
+        self.timer.reset();
+    }
+
+    fn tick(&mut self, delta: std::time::Duration) -> u32 {
+        self.timer.tick(delta);
+        let t = Easing::CubicOut.apply(self.timer.fraction());
+        self.shown = count(self.from, self.target, t);
+        self.shown
+    }
+}
+
+#[derive(Component)]
+pub struct ScoreText;
+
+/// A label that rises and fades out, used for merge scores and hints.
+#[derive(Component)]
+pub struct Popup {
+    timer: Timer,
+    origin: Vec2,
+}
+
+fn setup_score_panel(mut commands: Commands, themes: Res<Themes>) {
+    let panel = Text2dBundle {
+        text: Text::from_section(
+            "score 0",
+            TextStyle {
+                font: themes.font.clone(),
+                font_size: 30.0,
+                color: themes.current().text,
+            },
+        ),
+        text_anchor: Anchor::TopCenter,
+        transform: Transform {
+            translation: Vec2::new(0.0, 270.0).extend(0.1),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    commands.spawn(panel).insert(ScoreText).insert(ThemedText);
+}
+
+/// Spawns `text` as a child of `parent`, it rises from `origin` and fades.
+pub fn spawn_popup(
+    commands: &mut Commands,
+    themes: &Themes,
+    parent: Entity,
+    text: String,
+    origin: Vec2,
+) {
+    let popup = Text2dBundle {
+        text: Text::from_section(
+            text,
+            TextStyle {
+                font: themes.font.clone(),
+                font_size: 30.0,
+                color: themes.current().text,
+            },
+        ),
+        text_anchor: Anchor::Center,
+        transform: Transform {
+            translation: origin.extend(1.0),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let popup_id = commands
+        .spawn(popup)
+        .insert(Popup {
+            timer: Timer::from_seconds(POPUP_DURATION, TimerMode::Once),
+            origin,
+        })
+        .id();
+    commands.entity(parent).add_child(popup_id);
+}
+
+/// Spawns a rising "+N" label for every merge.
+fn spawn_merge_popups(
+    mut commands: Commands,
+    themes: Res<Themes>,
+    game: Res<GameState>,
+    mut event_reader: EventReader<Action>,
+) {
+    for action in event_reader.read() {
+        let Some((to, _)) = action.merged() else {
+            continue;
+        };
+        let text = format!("+{}", action.score());
+        spawn_popup(
+            &mut commands,
+            &themes,
+            game.board_entity,
+            text,
+            to_screen(&to),
+        );
+    }
+}
+
+fn update_popups(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut popups: Query<(Entity, &mut Popup, &mut Transform, &mut Text)>,
+) {
+    for (entity, mut popup, mut transform, mut text) in popups.iter_mut() {
+        popup.timer.tick(time.delta());
+        let t = popup.timer.fraction();
+        let rise = POPUP_RISE * Easing::CubicOut.apply(t);
+        transform.translation = (popup.origin + Vec2::new(0.0, rise)).extend(1.0);
+        for section in text.sections.iter_mut() {
+            section.style.color.set_a(1.0 - t);
+        }
+        if popup.timer.finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn reset_score_panel(mut display: ResMut<ScoreDisplay>, mut new_games: EventReader<NewGame>) {
+    if new_games.read().last().is_some() {
+        *display = ScoreDisplay::default();
+    }
+}
+
+fn update_score_panel(
+    time: Res<Time>,
+    game: Res<GameState>,
+    mut display: ResMut<ScoreDisplay>,
+    mut panels: Query<&mut Text, With<ScoreText>>,
+) {
+    if game.score != display.target {
+        display.retarget(game.score);
+    }
+    let shown = display.tick(time.delta());
+    for mut text in panels.iter_mut() {
+        text.sections[0].value = format!("score {}", shown);
+    }
+}
+
+pub struct ScorePlugin;
+
+impl Plugin for ScorePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ScoreDisplay>()
+            .add_systems(Startup, setup_score_panel)
+            .add_systems(
+                Update,
+                (
+                    reset_score_panel,
+                    (spawn_merge_popups, update_score_panel).run_if(resource_exists::<GameState>),
+                    update_popups,
+                )
+                    .chain(),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_count() {
+        assert_eq!(count(2, 4, 0.0), 2);
+        assert_eq!(count(2, 4, 0.5), 3);
+        assert_eq!(count(2, 4, 1.0), 4);
+        assert_eq!(count(2, 4, 7.0), 4);
+        assert_eq!(count(8, 0, 0.5), 4);
+    }
+
+    #[test]
+    fn test_score_display_catches_up() {
+        let mut display = ScoreDisplay::default();
+        display.retarget(100);
+        let halfway = display.tick(Duration::from_secs_f32(SCORE_COUNT_DURATION / 2.0));
+        assert!(halfway > 0 && halfway < 100, "{}", halfway);
+        display.retarget(200);
+        assert_eq!(display.from, halfway);
+        assert_eq!(display.tick(Duration::from_secs(1)), 200);
+    }
+}
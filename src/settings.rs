@@ -0,0 +1,391 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::accessibility::{Accessibility, Motion};
+use crate::animation::{AnimationConfig, TweenKind, Tweens};
+use crate::effects::EffectQuality;
+use crate::input::{Bindings, SwipeConfig};
+use crate::merge::MergeRule;
+use crate::mode::GameMode;
+use crate::sound::AudioSettings;
+use crate::theme::Themes;
+use crate::NewGame;
+
+pub const SETTINGS_FILE: &str = "settings.ron";
+/// Folder inside the platform config directory, e.g. `~/.config` on Linux.
+pub const CONFIG_DIR: &str = "animated_2048";
+/// Points to a different settings file, `none` turns saving off entirely.
+pub const SETTINGS_ENV: &str = "ANIMATED_2048_SETTINGS";
+
+pub const MIN_BOARD_SIZE: usize = 3;
+pub const MAX_BOARD_SIZE: usize = 6;
+pub const MIN_SPEED: f32 = 0.25;
+pub const MAX_SPEED: f32 = 4.0;
+/// Longest a single tween may take, in seconds.
+pub const MAX_TWEEN_DURATION: f32 = 1.0;
+
+/// Everything that survives a restart. Fields missing from the file keep
+/// their default, so older files keep loading when fields are added.
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub board_size: usize,
+    /// Mode of the next game.
+    pub mode: GameMode,
+    /// Whether the board of the next game wraps around its edges.
+    pub wrap: bool,
+    /// Which tiles merge in the next game.
+    pub merge: MergeRule,
+    pub animation_speed: f32,
+    /// Duration and curve of slides, merges and spawns.
+    pub tweens: Tweens,
+    pub theme: String,
+    pub volume: f32,
+    pub muted: bool,
+    pub contrast_text: bool,
+    pub badges: bool,
+    pub motion: Motion,
+    pub effects: EffectQuality,
+    pub last_seed: Option<u64>,
+    pub key_bindings: Bindings,
+    pub swipe: SwipeConfig,
+    /// Name on the high score table.
+    pub player_name: String,
+}
+
+/// The login name, the high score table has to call players something.
+fn default_player_name() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "player".to_string())
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        let audio = AudioSettings::default();
+        let accessibility = Accessibility::default();
+        Settings {
+            board_size: 4,
+            mode: GameMode::default(),
+            wrap: false,
+            merge: MergeRule::default(),
+            animation_speed: AnimationConfig::default().speed,
+            tweens: Tweens::default(),
+            theme: "classic".to_string(),
+            volume: audio.volume,
+            muted: audio.muted,
+            contrast_text: accessibility.contrast_text,
+            badges: accessibility.badges,
+            motion: accessibility.motion,
+            effects: EffectQuality::default(),
+            last_seed: None,
+            key_bindings: Bindings::default(),
+            swipe: SwipeConfig::default(),
+            player_name: default_player_name(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SettingsError {
+    Read(io::Error),
+    Parse(String),
+    Write(io::Error),
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SettingsError::Read(err) => write!(f, "could not read settings: {}", err),
+            SettingsError::Parse(err) => write!(f, "malformed settings: {}", err),
+            SettingsError::Write(err) => write!(f, "could not write settings: {}", err),
+        }
+    }
+}
+
+impl Settings {
+    pub fn parse(ron: &str) -> Result<Settings, SettingsError> {
+        ron::from_str::<Settings>(ron)
+            .map(Settings::sanitized)
+            .map_err(|err| SettingsError::Parse(err.to_string()))
+    }
+
+    pub fn to_ron(&self) -> String {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .expect("settings serialize")
+    }
+
+    /// Clamps values a hand edited file may have pushed out of range.
+    pub fn sanitized(self) -> Settings {
+        let mut tweens = self.tweens;
+        for kind in TweenKind::ALL {
+            let tween = tweens.get_mut(kind);
+            tween.duration = if tween.duration.is_finite() {
+                tween.duration.clamp(0.0, MAX_TWEEN_DURATION)
+            } else {
+                Tweens::default().get(kind).duration
+            };
+        }
+        Settings {
+            board_size: self.board_size.clamp(MIN_BOARD_SIZE, MAX_BOARD_SIZE),
+            animation_speed: if self.animation_speed.is_finite() {
+                self.animation_speed.clamp(MIN_SPEED, MAX_SPEED)
+            } else {
+                1.0
+            },
+            volume: if self.volume.is_finite() {
+                self.volume.clamp(0.0, 1.0)
+            } else {
+                AudioSettings::default().volume
+            },
+            tweens,
+            ..self
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Settings, SettingsError> {
+        let ron = fs::read_to_string(path).map_err(SettingsError::Read)?;
+        Settings::parse(&ron)
+    }
+
+    /// Loads `path`, falling back to the defaults with a warning. A missing
+    /// file is normal on the first start and only noted.
+    pub fn load_or_default(path: &Path) -> Settings {
+        match Settings::load(path) {
+            Ok(settings) => settings,
+            Err(SettingsError::Read(err)) if err.kind() == io::ErrorKind::NotFound => {
+                info!("no settings at {}, using defaults", path.display());
+                Settings::default()
+            }
+            Err(err) => {
+                warn!("{} ({}), using defaults", err, path.display());
+                Settings::default()
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), SettingsError> {
+        write_atomically(path, &self.to_ron()).map_err(SettingsError::Write)
+    }
+
+    /// Board size, mode, wrap, merge rule, seed, effects, input settings and the player
+    /// name live only here, everything else is read back from the resources
+    /// it was applied to.
+    fn collect(
+        &self,
+        themes: &Themes,
+        audio: &AudioSettings,
+        accessibility: &Accessibility,
+        config: &AnimationConfig,
+    ) -> Settings {
+        Settings {
+            board_size: self.board_size,
+            mode: self.mode,
+            wrap: self.wrap,
+            merge: self.merge,
+            animation_speed: config.speed,
+            tweens: config.tweens,
+            theme: themes.current().name.clone(),
+            volume: audio.volume,
+            muted: audio.muted,
+            contrast_text: accessibility.contrast_text,
+            badges: accessibility.badges,
+            motion: accessibility.motion,
+            effects: self.effects,
+            last_seed: self.last_seed,
+            key_bindings: self.key_bindings.clone(),
+            swipe: self.swipe,
+            player_name: self.player_name.clone(),
+        }
+    }
+}
+
+/// Writes to a temporary file first, so a crash never leaves a half written
+/// file behind. Missing folders are created.
+pub fn write_atomically(path: &Path, contents: &str) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("ron.tmp");
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, path)
+}
+
+/// Where the settings file lives, `None` when settings are not persisted.
+pub fn settings_path() -> Option<PathBuf> {
+    match std::env::var(SETTINGS_ENV) {
+        Ok(value) if value == "none" => None,
+        Ok(value) => Some(PathBuf::from(value)),
+        Err(_) => dirs::config_dir().map(|dir| dir.join(CONFIG_DIR).join(SETTINGS_FILE)),
+    }
+}
+
+#[derive(Resource, Debug)]
+pub struct SettingsPath(pub Option<PathBuf>);
+
+fn apply_settings(
+    settings: Res<Settings>,
+    asset_server: Res<AssetServer>,
+    mut themes: ResMut<Themes>,
+    mut audio: ResMut<AudioSettings>,
+    mut accessibility: ResMut<Accessibility>,
+    mut config: ResMut<AnimationConfig>,
+) {
+    if !themes.select_by_name(&settings.theme, &asset_server) {
+        warn!(
+            "unknown theme {}, keeping {}",
+            settings.theme,
+            themes.current().name
+        );
+    }
+    audio.volume = settings.volume;
+    audio.muted = settings.muted;
+    accessibility.contrast_text = settings.contrast_text;
+    accessibility.badges = settings.badges;
+    accessibility.motion = settings.motion;
+    config.speed = settings.animation_speed;
+    config.tweens = settings.tweens;
+}
+
+fn save_settings(
+    path: Res<SettingsPath>,
+    mut settings: ResMut<Settings>,
+    themes: Res<Themes>,
+    audio: Res<AudioSettings>,
+    accessibility: Res<Accessibility>,
+    config: Res<AnimationConfig>,
+    mut new_games: EventReader<NewGame>,
+) {
+    let mut current = settings.collect(&themes, &audio, &accessibility, &config);
+    if let Some(new_game) = new_games.read().last() {
+        if new_game.replay.is_none() && new_game.puzzle.is_none() && new_game.board.is_none() {
+            current.last_seed = Some(new_game.seed);
+        }
+    }
+    if current == *settings && !settings.is_changed() {
+        return;
+    }
+    *settings = current;
+    if let Some(path) = &path.0 {
+        if let Err(err) = settings.save(path) {
+            warn!("{} ({})", err, path.display());
+        }
+    }
+}
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        let path = settings_path();
+        let settings = path
+            .as_deref()
+            .map(Settings::load_or_default)
+            .unwrap_or_default();
+        app.insert_resource(settings)
+            .insert_resource(SettingsPath(path))
+            .add_systems(PreStartup, apply_settings)
+            .add_systems(Last, save_settings);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animation::{Easing, Tween};
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("animated_2048_{}_{}", name, std::process::id()))
+            .join(SETTINGS_FILE)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let settings = Settings {
+            board_size: 5,
+            theme: "dark".to_string(),
+            motion: Motion::Reduced,
+            last_seed: Some(2048),
+            ..Default::default()
+        };
+        assert_eq!(Settings::parse(&settings.to_ron()).unwrap(), settings);
+    }
+
+    #[test]
+    fn test_tweens_round_trip() {
+        let mut settings = Settings::default();
+        settings.tweens.merge = Tween::new(0.3, Easing::ElasticOut);
+        settings.tweens.spawn.easing = Easing::Spring;
+        assert!(settings.to_ron().contains("ElasticOut"));
+        let path = temp_path("tweens");
+        settings.save(&path).unwrap();
+        assert_eq!(Settings::load(&path).unwrap().tweens, settings.tweens);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+        let settings = Settings::parse(
+            "(tweens: (slide: (duration: 9.0, easing: CubicInOut), merge: (duration: inf, easing: Linear)))",
+        )
+        .unwrap();
+        assert_eq!(
+            settings.tweens.slide,
+            Tween::new(MAX_TWEEN_DURATION, Easing::CubicInOut)
+        );
+        assert_eq!(
+            settings.tweens.merge.duration,
+            Tweens::default().merge.duration
+        );
+        assert_eq!(settings.tweens.spawn, Tweens::default().spawn);
+    }
+
+    #[test]
+    fn test_missing_fields_use_defaults() {
+        let settings = Settings::parse("(theme: \"dark\")").unwrap();
+        assert_eq!(settings.theme, "dark");
+        assert_eq!(settings.board_size, Settings::default().board_size);
+    }
+
+    #[test]
+    fn test_out_of_range_values_are_clamped() {
+        let settings =
+            Settings::parse("(board_size: 100, volume: -1.0, animation_speed: 0.0)").unwrap();
+        assert_eq!(settings.board_size, MAX_BOARD_SIZE);
+        assert_eq!(settings.volume, 0.0);
+        assert_eq!(settings.animation_speed, MIN_SPEED);
+    }
+
+    #[test]
+    fn test_malformed_and_missing_files_fall_back() {
+        let path = temp_path("malformed");
+        assert!(matches!(Settings::load(&path), Err(SettingsError::Read(_))));
+        assert_eq!(Settings::load_or_default(&path), Settings::default());
+
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "(board_size: \"four\"").unwrap();
+        assert!(matches!(
+            Settings::load(&path),
+            Err(SettingsError::Parse(_))
+        ));
+        assert_eq!(Settings::load_or_default(&path), Settings::default());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = temp_path("save");
+        let settings = Settings {
+            volume: 0.3,
+            muted: true,
+            ..Default::default()
+        };
+        settings.save(&path).unwrap();
+        assert_eq!(Settings::load(&path).unwrap(), settings);
+        assert!(!path.with_extension("ron.tmp").exists());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,142 @@
+use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::utils::HashMap;
+
+use crate::theme::{ThemeSet, Themes};
+
+/// Texture pixels per board unit, so rounded corners stay smooth when the
+/// layout scales the board up.
+const RESOLUTION: f32 = 2.0;
+/// Alpha of drop shadows on a fully opaque tile.
+pub const SHADOW_ALPHA: f32 = 0.25;
+/// How far shadows fall below their tile, in board units.
+pub const SHADOW_OFFSET: Vec2 = Vec2::new(0.0, -4.0);
+
+/// Sprites with a `custom_size` whose corners are rounded with the current
+/// theme's `corner_radius`.
+#[derive(Component)]
+pub struct Rounded;
+
+/// A drop shadow underneath a tile or the board.
+#[derive(Component)]
+pub struct Shadow;
+
+/// A white rectangle of `size` pixels with corners of `radius` pixels cut
+/// round. Edge pixels are partly covered for a bit of anti aliasing.
+pub fn rounded_rect(size: UVec2, radius: f32) -> Image {
+    let radius = radius.clamp(0.0, size.min_element() as f32 / 2.0);
+    let half = size.as_vec2() / 2.0;
+    let mut data = Vec::with_capacity((size.x * size.y * 4) as usize);
+    for y in 0..size.y {
+        for x in 0..size.x {
+            // signed distance of the pixel center to the rounded outline
+            let p = (Vec2::new(x as f32, y as f32) + 0.5 - half).abs();
+            let q = p - (half - Vec2::splat(radius));
+            let distance = q.max(Vec2::ZERO).length() + q.max_element().min(0.0) - radius;
+            let coverage = (0.5 - distance).clamp(0.0, 1.0);
+            data.extend_from_slice(&[255, 255, 255, (coverage * 255.0).round() as u8]);
+        }
+    }
+    Image::new(
+        Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+}
+
+/// Generated textures by pixel size and radius, tiles all share one.
+#[derive(Resource, Default)]
+pub struct Shapes(HashMap<(UVec2, u32), Handle<Image>>);
+
+impl Shapes {
+    pub fn get(&mut self, images: &mut Assets<Image>, size: Vec2, radius: f32) -> Handle<Image> {
+        let pixels = (size * RESOLUTION).round().as_uvec2().max(UVec2::ONE);
+        let radius = (radius * RESOLUTION).round() as u32;
+        self.0
+            .entry((pixels, radius))
+            .or_insert_with(|| images.add(rounded_rect(pixels, radius as f32)))
+            .clone()
+    }
+}
+
+/// Gives new rounded sprites their texture, and all of them a new one when
+/// the theme changes its corner radius.
+fn round_sprites(
+    themes: Res<Themes>,
+    mut shapes: ResMut<Shapes>,
+    mut images: ResMut<Assets<Image>>,
+    mut radius: Local<Option<f32>>,
+    mut sprites: Query<(Ref<Rounded>, &Sprite, &mut Handle<Image>)>,
+) {
+    let corner_radius = themes.current().corner_radius;
+    let all = *radius != Some(corner_radius);
+    *radius = Some(corner_radius);
+    for (rounded, sprite, mut texture) in sprites.iter_mut() {
+        if !all && !rounded.is_added() {
+            continue;
+        }
+        if let Some(size) = sprite.custom_size {
+            *texture = shapes.get(&mut images, size, corner_radius);
+        }
+    }
+}
+
+pub struct ShapePlugin;
+
+impl Plugin for ShapePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Shapes>()
+            .add_systems(PostUpdate, round_sprites.after(ThemeSet));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alpha(image: &Image, x: u32, y: u32) -> u8 {
+        let width = image.texture_descriptor.size.width;
+        image.data[((y * width + x) * 4 + 3) as usize]
+    }
+
+    #[test]
+    fn test_rounded_corners() {
+        let image = rounded_rect(UVec2::new(40, 20), 8.0);
+        assert_eq!(alpha(&image, 0, 0), 0);
+        assert_eq!(alpha(&image, 39, 19), 0);
+        assert_eq!(alpha(&image, 20, 10), 255);
+        // straight edges are not affected
+        assert_eq!(alpha(&image, 20, 0), 255);
+        assert_eq!(alpha(&image, 0, 10), 255);
+    }
+
+    #[test]
+    fn test_radius_is_limited_to_half_the_size() {
+        let square = rounded_rect(UVec2::splat(10), 0.0);
+        assert!(square.data.chunks(4).all(|pixel| pixel[3] == 255));
+        let circle = rounded_rect(UVec2::splat(10), 100.0);
+        assert_eq!(alpha(&circle, 0, 0), 0);
+        assert_eq!(alpha(&circle, 5, 5), 255);
+        // the top edge of a circle is only touched, not cut off
+        assert!(alpha(&circle, 5, 0) > 200);
+    }
+
+    #[test]
+    fn test_shapes_are_shared() {
+        let mut images = Assets::<Image>::default();
+        let mut shapes = Shapes::default();
+        let a = shapes.get(&mut images, Vec2::splat(100.0), 6.0);
+        let b = shapes.get(&mut images, Vec2::splat(100.0), 6.0);
+        let c = shapes.get(&mut images, Vec2::splat(100.0), 3.0);
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(images.len(), 2);
+    }
+}
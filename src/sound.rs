@@ -0,0 +1,354 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+use bevy::audio::{AddAudioSource, AudioPlugin, Source, Volume};
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use crate::action::Action;
+use crate::merge::MergeRule;
+use crate::mode::ModeEnd;
+use crate::{GameOutcome, GameState};
+
+pub const SAMPLE_RATE: u32 = 44_100;
+/// Set to `null` to keep the game silent, e.g. on CI machines.
+pub const BACKEND_ENV: &str = "ANIMATED_2048_AUDIO";
+
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SoundEffect {
+    Slide,
+    /// Carries the exponent of the merged value, higher merges sound higher.
+    Merge(u32),
+    Spawn,
+    Win,
+    Lose,
+}
+
+/// Picks the sounds for a batch of actions. A move plays a single slide and
+/// a single merge sound no matter how many tiles were involved. Merges
+/// sound like the classic tile on the same rung of `rule`'s ladder.
+pub fn effects_for(actions: &[Action], rule: MergeRule) -> Vec<SoundEffect> {
+    let mut effects = vec![];
+    let slid = actions
+        .iter()
+        .any(|action| matches!(action, Action::SlideTile(..)));
+    let merged = actions
+        .iter()
+        .filter_map(|action| action.merged())
+        .map(|(_, value)| rule.classic(&value).to_exponent())
+        .max();
+    let spawned = actions
+        .iter()
+        .any(|action| matches!(action, Action::SpawnRandomTile(..)));
+    if let Some(exponent) = merged {
+        effects.push(SoundEffect::Merge(exponent));
+    } else if slid {
+        effects.push(SoundEffect::Slide);
+    }
+    if spawned {
+        effects.push(SoundEffect::Spawn);
+    }
+    effects
+}
+
+fn envelope(i: usize, len: usize, attack: f32, decay: f32) -> f32 {
+    let t = i as f32 / SAMPLE_RATE as f32;
+    let attack = (t / attack).min(1.0);
+    let release = 1.0 - i as f32 / len as f32;
+    attack * (-t * decay).exp() * release
+}
+
+fn tone(freq: f32, seconds: f32, glide: f32, gain: f32) -> Vec<f32> {
+    let len = (seconds * SAMPLE_RATE as f32) as usize;
+    let mut phase = 0.0;
+    (0..len)
+        .map(|i| {
+            let progress = i as f32 / len as f32;
+            phase += 2.0 * PI * freq * (1.0 + glide * progress) / SAMPLE_RATE as f32;
+            let partials = phase.sin() + 0.3 * (2.0 * phase).sin();
+            gain * partials / 1.3 * envelope(i, len, 0.005, 12.0)
+        })
+        .collect()
+}
+
+fn noise(seconds: f32, gain: f32) -> Vec<f32> {
+    let len = (seconds * SAMPLE_RATE as f32) as usize;
+    let mut state: u32 = 0x2048;
+    let mut low = 0.0;
+    (0..len)
+        .map(|i| {
+            // xorshift keeps the noise identical on every run
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            let white = state as f32 / u32::MAX as f32 * 2.0 - 1.0;
+            low += (white - low) * 0.15;
+            gain * low * envelope(i, len, 0.01, 20.0)
+        })
+        .collect()
+}
+
+fn sequence(freqs: &[f32], seconds: f32, gain: f32) -> Vec<f32> {
+    freqs
+        .iter()
+        .flat_map(|freq| tone(*freq, seconds, 0.0, gain))
+        .collect()
+}
+
+/// Renders `effect` as mono samples in `[-1, 1]` at `SAMPLE_RATE`.
+pub fn synthesize(effect: &SoundEffect) -> Vec<f32> {
+    match effect {
+        SoundEffect::Slide => noise(0.08, 0.6),
+        SoundEffect::Merge(exponent) => {
+            let freq = 220.0 * 2f32.powf(*exponent as f32 / 4.0);
+            tone(freq, 0.15, 0.1, 0.5)
+        }
+        SoundEffect::Spawn => tone(880.0, 0.05, 0.0, 0.2),
+        SoundEffect::Win => sequence(&[523.3, 659.3, 784.0, 1046.5], 0.12, 0.5),
+        SoundEffect::Lose => sequence(&[392.0, 329.6, 261.6, 196.0], 0.15, 0.5),
+    }
+}
+
+/// A sound generated in code, played through bevy's audio pipeline.
+#[derive(Asset, TypePath, Clone)]
+pub struct Synth {
+    samples: Arc<[f32]>,
+}
+
+pub struct SynthDecoder {
+    samples: Arc<[f32]>,
+    index: usize,
+}
+
+impl Iterator for SynthDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.samples.get(self.index).copied();
+        self.index += 1;
+        sample
+    }
+}
+
+impl Source for SynthDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        Some(self.samples.len().saturating_sub(self.index))
+    }
+    fn channels(&self) -> u16 {
+        1
+    }
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        Some(std::time::Duration::from_secs_f32(
+            self.samples.len() as f32 / SAMPLE_RATE as f32,
+        ))
+    }
+}
+
+impl Decodable for Synth {
+    type DecoderItem = f32;
+    type Decoder = SynthDecoder;
+
+    fn decoder(&self) -> Self::Decoder {
+        SynthDecoder {
+            samples: self.samples.clone(),
+            index: 0,
+        }
+    }
+}
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct AudioSettings {
+    pub volume: f32,
+    pub muted: bool,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        AudioSettings {
+            volume: 0.7,
+            muted: false,
+        }
+    }
+}
+
+impl AudioSettings {
+    pub fn effective_volume(&self) -> f32 {
+        if self.muted {
+            0.0
+        } else {
+            self.volume.clamp(0.0, 1.0)
+        }
+    }
+}
+
+/// Where sound effects end up. `Null` swallows them, which keeps headless
+/// runs and tests working without an audio device.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoundBackend {
+    Bevy,
+    Null,
+}
+
+/// Synthesized sounds, rendered once on first use.
+#[derive(Resource, Default)]
+struct SoundBank(HashMap<SoundEffect, Handle<Synth>>);
+
+fn queue_action_sounds(
+    game: Option<Res<GameState>>,
+    mut actions: EventReader<Action>,
+    mut outcomes: EventReader<GameOutcome>,
+    mut effects: EventWriter<SoundEffect>,
+) {
+    let actions: Vec<Action> = actions.read().cloned().collect();
+    let rule = game.map(|game| game.board.rule).unwrap_or_default();
+    effects.send_batch(effects_for(&actions, rule));
+    for outcome in outcomes.read() {
+        effects.send(match outcome {
+            GameOutcome::Won | GameOutcome::Ended(ModeEnd::TargetReached) => SoundEffect::Win,
+            GameOutcome::Lost | GameOutcome::Ended(_) => SoundEffect::Lose,
+        });
+    }
+}
+
+fn play_sound_effects(
+    mut commands: Commands,
+    backend: Res<SoundBackend>,
+    settings: Res<AudioSettings>,
+    mut bank: ResMut<SoundBank>,
+    synths: Option<ResMut<Assets<Synth>>>,
+    mut effects: EventReader<SoundEffect>,
+) {
+    let volume = settings.effective_volume();
+    let (SoundBackend::Bevy, Some(mut synths)) = (*backend, synths) else {
+        effects.clear();
+        return;
+    };
+    if volume <= 0.0 {
+        effects.clear();
+        return;
+    }
+    for effect in effects.read() {
+        let handle = bank
+            .0
+            .entry(*effect)
+            .or_insert_with(|| {
+                synths.add(Synth {
+                    samples: synthesize(effect).into(),
+                })
+            })
+            .clone();
+        commands.spawn(AudioSourceBundle {
+            source: handle,
+            settings: PlaybackSettings::DESPAWN.with_volume(Volume::new(volume)),
+        });
+    }
+}
+
+pub struct SoundPlugin;
+
+impl Plugin for SoundPlugin {
+    fn build(&self, app: &mut App) {
+        let wants_null = std::env::var(BACKEND_ENV).is_ok_and(|value| value == "null");
+        let backend = if wants_null || !app.is_plugin_added::<AudioPlugin>() {
+            SoundBackend::Null
+        } else {
+            app.add_audio_source::<Synth>();
+            SoundBackend::Bevy
+        };
+        app.insert_resource(backend)
+            .init_resource::<AudioSettings>()
+            .init_resource::<SoundBank>()
+            .add_event::<SoundEffect>()
+            .add_systems(
+                PostUpdate,
+                (queue_action_sounds, play_sound_effects).chain(),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+    use crate::direction::Direction;
+
+    fn zero_crossings(samples: &[f32]) -> usize {
+        samples
+            .windows(2)
+            .filter(|pair| pair[0] < 0.0 && pair[1] >= 0.0)
+            .count()
+    }
+
+    #[test]
+    fn test_effects_for_move() {
+        let board: Board = "1120300000000000".parse().unwrap();
+        let actions = board.plan_slide_and_merge(&Direction::Left);
+        assert_eq!(
+            effects_for(&actions, MergeRule::Classic),
+            vec![SoundEffect::Merge(2)]
+        );
+
+        let board: Board = "0102000000000000".parse().unwrap();
+        let actions = board.plan_slide_and_merge(&Direction::Left);
+        assert_eq!(
+            effects_for(&actions, MergeRule::Classic),
+            vec![SoundEffect::Slide]
+        );
+        assert_eq!(effects_for(&[], MergeRule::Classic), vec![]);
+
+        // a 1 and a 2 make a 3, the third rung
+        let board = Board::parse_with("1200000000000000", MergeRule::Threes).unwrap();
+        let actions = board.plan_slide_and_merge(&Direction::Left);
+        assert_eq!(
+            effects_for(&actions, MergeRule::Threes),
+            vec![SoundEffect::Merge(3)]
+        );
+    }
+
+    #[test]
+    fn test_synthesized_samples_are_bounded() {
+        let effects = [
+            SoundEffect::Slide,
+            SoundEffect::Merge(1),
+            SoundEffect::Merge(11),
+            SoundEffect::Spawn,
+            SoundEffect::Win,
+            SoundEffect::Lose,
+        ];
+        for effect in effects.iter() {
+            let samples = synthesize(effect);
+            assert!(!samples.is_empty(), "{:?}", effect);
+            assert!(
+                samples.iter().all(|s| s.is_finite() && s.abs() <= 1.0),
+                "{:?}",
+                effect
+            );
+        }
+    }
+
+    #[test]
+    fn test_merge_pitch_rises_with_exponent() {
+        let low = zero_crossings(&synthesize(&SoundEffect::Merge(1)));
+        let high = zero_crossings(&synthesize(&SoundEffect::Merge(8)));
+        assert!(high > low, "{} <= {}", high, low);
+    }
+
+    #[test]
+    fn test_muted_volume() {
+        let mut settings = AudioSettings::default();
+        assert!(settings.effective_volume() > 0.0);
+        settings.muted = true;
+        assert_eq!(settings.effective_volume(), 0.0);
+    }
+
+    #[test]
+    fn test_decoder_plays_all_samples() {
+        let synth = Synth {
+            samples: synthesize(&SoundEffect::Spawn).into(),
+        };
+        assert_eq!(synth.decoder().count(), synth.samples.len());
+    }
+}
@@ -0,0 +1,127 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::action::Action;
+use crate::board::Board;
+use crate::merge::MergeRule;
+use crate::tile::position::Position;
+use crate::tile::value::Value;
+use crate::tile::Tile;
+
+/// A tile a puzzle puts on the board after a move. `value` is written in
+/// board notation, e.g. `'1'` for a 2 under classic rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Spawn {
+    pub row: usize,
+    pub col: usize,
+    pub value: char,
+}
+
+impl Spawn {
+    pub fn value(&self, rule: MergeRule) -> Option<Value> {
+        rule.parse(&self.value.to_string())
+            .filter(|value| matches!(value, Value::Number(_)))
+    }
+}
+
+/// Where new tiles come from: the spawns of a script first, random tiles of
+/// the board's rule after that, 2s and 4s under classic rules.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Spawner {
+    /// The remaining spawns of the script, the next one last.
+    script: Vec<Spawn>,
+    /// Nothing spawns once the script runs out without one.
+    rng: Option<ChaCha8Rng>,
+}
+
+impl Spawner {
+    pub fn seeded(seed: u64) -> Spawner {
+        Spawner {
+            script: vec![],
+            rng: Some(ChaCha8Rng::seed_from_u64(seed)),
+        }
+    }
+
+    pub fn script(spawns: &[Spawn]) -> Spawner {
+        Spawner {
+            script: vec![],
+            rng: None,
+        }
+        .starting_with(spawns)
+    }
+
+    /// Plays `spawns` before the tiles this spawner would spawn.
+    pub fn starting_with(mut self, spawns: &[Spawn]) -> Spawner {
+        self.script.extend(spawns.iter().rev());
+        self
+    }
+
+    /// The next tile for `board`. A scripted spawn whose cell is taken goes
+    /// to the next empty cell in reading order instead.
+    pub fn plan(&mut self, board: &Board) -> Option<Action> {
+        let Some(spawn) = self.script.pop() else {
+            return board.plan_spawn_random_tile(self.rng.as_mut()?);
+        };
+        let value = spawn.value(board.rule)?;
+        let cells = board.size * board.size;
+        let start = spawn.row * board.size + spawn.col;
+        let position = (0..cells)
+            .map(|offset| {
+                let cell = (start + offset) % cells;
+                Position {
+                    row: cell / board.size,
+                    col: cell % board.size,
+                }
+            })
+            .find(|position| board.get_value(position) == Value::Empty)?;
+        Some(Action::SpawnRandomTile(Tile { value, position }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spawn(row: usize, col: usize, value: char) -> Spawn {
+        Spawn { row, col, value }
+    }
+
+    #[test]
+    fn test_scripts_spawn_in_order() {
+        let mut board: Board = "1000000000000000".parse().unwrap();
+        let mut spawner = Spawner::script(&[spawn(3, 3, '2'), spawn(0, 0, '1')]);
+        let first = spawner.plan(&board).unwrap();
+        board.apply(first);
+        assert_eq!(board.to_string(), "1000000000000002");
+        // the taken corner moves the second spawn along
+        let second = spawner.plan(&board).unwrap();
+        board.apply(second);
+        assert_eq!(board.to_string(), "1100000000000002");
+        assert!(spawner.plan(&board).is_none());
+    }
+
+    #[test]
+    fn test_scripts_go_before_random_spawns() {
+        let board = Board::new(4);
+        let mut spawner = Spawner::seeded(3).starting_with(&[spawn(2, 1, '3')]);
+        let Some(Action::SpawnRandomTile(first)) = spawner.plan(&board) else {
+            panic!("no scripted spawn");
+        };
+        assert_eq!(first.value, Value::Number(8));
+        assert_eq!(first.position, Position { row: 2, col: 1 });
+        let random = spawner.plan(&board);
+        assert_eq!(
+            format!("{:?}", random),
+            format!("{:?}", Spawner::seeded(3).plan(&board))
+        );
+    }
+
+    #[test]
+    fn test_seeded_spawns_repeat() {
+        let board = Board::new(4);
+        let a = Spawner::seeded(3).plan(&board);
+        let b = Spawner::seeded(3).plan(&board);
+        assert_eq!(format!("{:?}", a), format!("{:?}", b));
+    }
+}
@@ -0,0 +1,616 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::action::Action;
+use crate::direction::Direction;
+use crate::merge::MergeRule;
+use crate::mode::GameMode;
+use crate::settings::{settings_path, write_atomically};
+use crate::theme::Theme;
+use crate::tile::value::Value;
+use crate::{GameOutcome, GameState, NewGame, Turn, Undone};
+
+/// Lives next to the settings file.
+pub const STATS_FILE: &str = "stats.ron";
+/// Width of the longest bar in the text report, in characters.
+const REPORT_BAR_WIDTH: usize = 30;
+/// Length of the longest bar on the stats screen, in pixels.
+const CHART_BAR_LENGTH: f32 = 180.0;
+
+const DIRECTIONS: [Direction; 4] = [
+    Direction::Left,
+    Direction::Right,
+    Direction::Up,
+    Direction::Down,
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DirectionCounts {
+    pub left: u64,
+    pub right: u64,
+    pub up: u64,
+    pub down: u64,
+}
+
+impl DirectionCounts {
+    pub fn get(&self, direction: Direction) -> u64 {
+        match direction {
+            Direction::Left => self.left,
+            Direction::Right => self.right,
+            Direction::Up => self.up,
+            Direction::Down => self.down,
+        }
+    }
+
+    fn get_mut(&mut self, direction: Direction) -> &mut u64 {
+        match direction {
+            Direction::Left => &mut self.left,
+            Direction::Right => &mut self.right,
+            Direction::Up => &mut self.up,
+            Direction::Down => &mut self.down,
+        }
+    }
+}
+
+/// The game that is being played, it is added to `Stats` once it is over.
+#[derive(Resource, Debug, Clone, Default, PartialEq)]
+pub struct CurrentGame {
+    pub moves: u64,
+    pub max_tile: u32,
+    pub won: bool,
+    pub over: bool,
+}
+
+impl CurrentGame {
+    /// Raises the largest tile to the one `action` creates. Tiles are kept
+    /// as the classic tile on their rung so games under different merge
+    /// rules share one histogram.
+    pub fn record_action(&mut self, action: &Action, rule: MergeRule) {
+        let created = match action {
+            Action::SpawnRandomTile(tile) => Some(tile.value),
+            _ => action.merged().map(|(_, value)| value),
+        };
+        if let Some(Value::Number(tile)) = created.map(|value| rule.classic(&value)) {
+            self.max_tile = self.max_tile.max(tile);
+        }
+    }
+}
+
+/// Whether games of `mode` count, edited positions and puzzles do not.
+fn keeps_stats(mode: GameMode) -> bool {
+    !matches!(mode, GameMode::Sandbox | GameMode::Puzzle { .. })
+}
+
+/// Lifetime statistics over all games. A game counts once it is lost,
+/// replaced by a new one or the app quits.
+#[derive(Resource, Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Stats {
+    pub games_played: u64,
+    pub games_won: u64,
+    pub best_score: u32,
+    pub highest_tile: u32,
+    pub total_moves: u64,
+    pub moves: DirectionCounts,
+    /// Moves of finished games only, for the average game length.
+    pub finished_game_moves: u64,
+    /// How many games ended with each largest tile.
+    pub max_tiles: BTreeMap<u32, u64>,
+}
+
+impl Stats {
+    pub fn parse(ron: &str) -> Result<Stats, String> {
+        ron::from_str(ron).map_err(|err| err.to_string())
+    }
+
+    pub fn to_ron(&self) -> String {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .expect("stats serialize")
+    }
+
+    /// Loads `path`, starting from zero when there is no file yet or it
+    /// cannot be read.
+    pub fn load_or_default(path: &Path) -> Stats {
+        match fs::read_to_string(path) {
+            Ok(ron) => Stats::parse(&ron).unwrap_or_else(|err| {
+                warn!("malformed stats: {} ({})", err, path.display());
+                Stats::default()
+            }),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Stats::default(),
+            Err(err) => {
+                warn!("could not read stats: {} ({})", err, path.display());
+                Stats::default()
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        write_atomically(path, &self.to_ron())
+    }
+
+    pub fn record_move(&mut self, direction: Direction) {
+        self.total_moves += 1;
+        *self.moves.get_mut(direction) += 1;
+    }
+
+    /// Takes back a move that was undone.
+    pub fn undo_move(&mut self, direction: Direction) {
+        self.total_moves = self.total_moves.saturating_sub(1);
+        let count = self.moves.get_mut(direction);
+        *count = count.saturating_sub(1);
+    }
+
+    /// Records `score` of a game under `rule` and its largest tile. Scores
+    /// of other merge rules cannot be compared with classic ones, so only
+    /// their tiles count.
+    pub fn record_board(&mut self, score: u32, rule: MergeRule, max_tile: u32) {
+        if rule == MergeRule::Classic {
+            self.best_score = self.best_score.max(score);
+        }
+        self.highest_tile = self.highest_tile.max(max_tile);
+    }
+
+    /// Counts `game` unless it is already counted or was never played.
+    /// Returns whether it was counted.
+    pub fn finish_game(&mut self, game: &mut CurrentGame) -> bool {
+        if game.over || game.moves == 0 {
+            return false;
+        }
+        game.over = true;
+        self.games_played += 1;
+        if game.won {
+            self.games_won += 1;
+        }
+        self.finished_game_moves += game.moves;
+        *self.max_tiles.entry(game.max_tile).or_default() += 1;
+        true
+    }
+
+    pub fn average_game_length(&self) -> f32 {
+        if self.games_played == 0 {
+            0.0
+        } else {
+            self.finished_game_moves as f32 / self.games_played as f32
+        }
+    }
+
+    /// Label and value of every single number stat.
+    pub fn summary(&self) -> Vec<(&'static str, String)> {
+        let win_rate = if self.games_played == 0 {
+            0.0
+        } else {
+            self.games_won as f32 / self.games_played as f32 * 100.0
+        };
+        vec![
+            ("games played", self.games_played.to_string()),
+            (
+                "games won",
+                format!("{} ({:.0}%)", self.games_won, win_rate),
+            ),
+            ("best score", self.best_score.to_string()),
+            ("highest tile", self.highest_tile.to_string()),
+            ("total moves", self.total_moves.to_string()),
+            (
+                "average game",
+                format!("{:.1} moves", self.average_game_length()),
+            ),
+        ]
+    }
+
+    pub fn direction_chart(&self) -> Vec<(String, u64)> {
+        DIRECTIONS
+            .iter()
+            .map(|direction| {
+                (
+                    format!("{:?}", direction).to_lowercase(),
+                    self.moves.get(*direction),
+                )
+            })
+            .collect()
+    }
+
+    /// The max tile histogram from the smallest to the largest tile.
+    pub fn tile_chart(&self) -> Vec<(String, u64)> {
+        self.max_tiles
+            .iter()
+            .map(|(tile, count)| (tile.to_string(), *count))
+            .collect()
+    }
+
+    /// Everything as plain text with bar charts made of `#`.
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        for (label, value) in self.summary() {
+            writeln!(report, "{:<14}{}", label, value).unwrap();
+        }
+        for (title, chart) in [
+            ("moves per direction", self.direction_chart()),
+            ("highest tile per game", self.tile_chart()),
+        ] {
+            writeln!(report, "\n{}", title).unwrap();
+            let max = chart.iter().map(|(_, count)| *count).max().unwrap_or(0);
+            for (label, count) in chart {
+                let bar = "#".repeat(bar_length(count, max, REPORT_BAR_WIDTH as f32) as usize);
+                writeln!(
+                    report,
+                    "{:>6} {:<width$} {}",
+                    label,
+                    bar,
+                    count,
+                    width = REPORT_BAR_WIDTH
+                )
+                .unwrap();
+            }
+        }
+        report
+    }
+}
+
+/// Length of the bar for `count` when `max` gets `full` length. Non zero
+/// counts always get a sliver.
+pub fn bar_length(count: u64, max: u64, full: f32) -> f32 {
+    if count == 0 || max == 0 {
+        0.0
+    } else {
+        (count as f32 / max as f32 * full).round().max(1.0)
+    }
+}
+
+/// Where the stats file lives, `None` when settings are not persisted.
+pub fn stats_path() -> Option<PathBuf> {
+    settings_path().map(|path| path.with_file_name(STATS_FILE))
+}
+
+#[derive(Resource, Debug)]
+pub struct StatsPath(pub Option<PathBuf>);
+
+/// Prints the stats for the `stats` command line subcommand.
+pub fn print_stats() {
+    match stats_path() {
+        Some(path) => {
+            println!("stats from {}\n", path.display());
+            print!("{}", Stats::load_or_default(&path).report());
+        }
+        None => println!("stats are not saved"),
+    }
+}
+
+/// Spawns the numbers and both charts of the stats screen into `parent`.
+pub fn spawn_stats_charts(
+    parent: &mut ChildBuilder,
+    stats: &Stats,
+    theme: &Theme,
+    font: &Handle<Font>,
+) {
+    let text = |value: String, font_size: f32| {
+        TextBundle::from_section(
+            value,
+            TextStyle {
+                font: font.clone(),
+                font_size,
+                color: theme.text,
+            },
+        )
+    };
+    let column = |align_items: AlignItems| NodeBundle {
+        style: Style {
+            flex_direction: FlexDirection::Column,
+            align_items,
+            row_gap: Val::Px(6.0),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let row = |align_items: AlignItems, gap: f32| NodeBundle {
+        style: Style {
+            flex_direction: FlexDirection::Row,
+            align_items,
+            column_gap: Val::Px(gap),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let bar = |width: f32, height: f32, color: Color| NodeBundle {
+        style: Style {
+            width: Val::Px(width),
+            height: Val::Px(height),
+            ..Default::default()
+        },
+        background_color: color.into(),
+        ..Default::default()
+    };
+
+    parent
+        .spawn(row(AlignItems::FlexStart, 48.0))
+        .with_children(|charts| {
+            charts
+                .spawn(column(AlignItems::FlexStart))
+                .with_children(|numbers| {
+                    for (label, value) in stats.summary() {
+                        numbers.spawn(text(format!("{}: {}", label, value), 22.0));
+                    }
+                });
+
+            charts
+                .spawn(column(AlignItems::FlexStart))
+                .with_children(|directions| {
+                    directions.spawn(text("moves per direction".to_string(), 22.0));
+                    let chart = stats.direction_chart();
+                    let max = chart.iter().map(|(_, count)| *count).max().unwrap_or(0);
+                    for (label, count) in chart {
+                        directions
+                            .spawn(row(AlignItems::Center, 8.0))
+                            .with_children(|line| {
+                                line.spawn(text(label, 18.0)).insert(Style {
+                                    width: Val::Px(50.0),
+                                    ..Default::default()
+                                });
+                                line.spawn(bar(
+                                    bar_length(count, max, CHART_BAR_LENGTH),
+                                    16.0,
+                                    theme.board,
+                                ));
+                                line.spawn(text(count.to_string(), 18.0));
+                            });
+                    }
+                });
+
+            charts
+                .spawn(column(AlignItems::FlexStart))
+                .with_children(|tiles| {
+                    tiles.spawn(text("highest tile per game".to_string(), 22.0));
+                    let chart = stats.tile_chart();
+                    let max = chart.iter().map(|(_, count)| *count).max().unwrap_or(0);
+                    tiles
+                        .spawn(row(AlignItems::FlexEnd, 6.0))
+                        .with_children(|bars| {
+                            for (label, count) in chart {
+                                let color = label
+                                    .parse()
+                                    .map(|tile| theme.tile_color(&Value::Number(tile)))
+                                    .unwrap_or(theme.board);
+                                bars.spawn(column(AlignItems::Center))
+                                    .with_children(|column| {
+                                        column.spawn(text(count.to_string(), 16.0));
+                                        column.spawn(bar(
+                                            32.0,
+                                            bar_length(count, max, CHART_BAR_LENGTH * 0.75),
+                                            color,
+                                        ));
+                                        column.spawn(text(label, 16.0));
+                                    });
+                            }
+                        });
+                });
+        });
+}
+
+/// Follows the running game through its turns, actions and outcome and adds
+/// it to the stats once it is over. Stats are saved whenever a game is
+/// counted.
+#[allow(clippy::too_many_arguments)]
+fn track_stats(
+    path: Res<StatsPath>,
+    game: Option<Res<GameState>>,
+    mut stats: ResMut<Stats>,
+    mut current: ResMut<CurrentGame>,
+    mut new_games: EventReader<NewGame>,
+    mut turns: EventReader<Turn>,
+    mut undone: EventReader<Undone>,
+    mut actions: EventReader<Action>,
+    mut outcomes: EventReader<GameOutcome>,
+    mut exits: EventReader<AppExit>,
+) {
+    let mut finished = false;
+    // the new game has already replaced the old one, which is counted with
+    // what was seen of it up to the last frame
+    if new_games.read().last().is_some() {
+        finished |= stats.finish_game(&mut current);
+        *current = CurrentGame::default();
+    }
+    // replays were counted when they were played
+    let game = game.filter(|game| !game.watching && keeps_stats(game.mode));
+    match game {
+        Some(game) => {
+            let mut scored = false;
+            for action in actions.read() {
+                current.record_action(action, game.board.rule);
+                scored = true;
+            }
+            if scored {
+                stats.record_board(game.score, game.board.rule, current.max_tile);
+            }
+            for Turn(direction) in turns.read() {
+                current.moves += 1;
+                stats.record_move(*direction);
+            }
+            for Undone(direction) in undone.read() {
+                current.moves = current.moves.saturating_sub(1);
+                stats.undo_move(*direction);
+            }
+        }
+        None => {
+            actions.clear();
+            turns.clear();
+            undone.clear();
+        }
+    }
+    for outcome in outcomes.read() {
+        match outcome {
+            GameOutcome::Won => current.won = true,
+            GameOutcome::Lost | GameOutcome::Ended(_) => {
+                finished |= stats.finish_game(&mut current)
+            }
+        }
+    }
+    if exits.read().last().is_some() {
+        stats.finish_game(&mut current);
+        finished = true;
+    }
+    if !finished {
+        return;
+    }
+    if let Some(path) = &path.0 {
+        if let Err(err) = stats.save(path) {
+            warn!("could not write stats: {} ({})", err, path.display());
+        }
+    }
+}
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        let path = stats_path();
+        let stats = path
+            .as_deref()
+            .map(Stats::load_or_default)
+            .unwrap_or_default();
+        app.insert_resource(stats)
+            .insert_resource(StatsPath(path))
+            .init_resource::<CurrentGame>()
+            .add_systems(Last, track_stats);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tile::{position::Position, Tile};
+
+    fn game(moves: u64, max_tile: u32, won: bool) -> CurrentGame {
+        CurrentGame {
+            moves,
+            max_tile,
+            won,
+            over: false,
+        }
+    }
+
+    #[test]
+    fn test_finished_games_are_counted_once() {
+        let mut stats = Stats::default();
+        let mut first = game(100, 512, false);
+        assert!(stats.finish_game(&mut first));
+        assert!(!stats.finish_game(&mut first));
+        assert!(stats.finish_game(&mut game(300, 2048, true)));
+        assert!(stats.finish_game(&mut game(200, 512, false)));
+        // games without a single move were never played
+        assert!(!stats.finish_game(&mut game(0, 4, false)));
+
+        assert_eq!(stats.games_played, 3);
+        assert_eq!(stats.games_won, 1);
+        assert_eq!(stats.average_game_length(), 200.0);
+        assert_eq!(
+            stats.tile_chart(),
+            vec![("512".to_string(), 2), ("2048".to_string(), 1)]
+        );
+    }
+
+    #[test]
+    fn test_moves_and_records() {
+        let mut stats = Stats::default();
+        stats.record_move(Direction::Left);
+        stats.record_move(Direction::Left);
+        stats.record_move(Direction::Down);
+        assert_eq!(stats.total_moves, 3);
+        assert_eq!(stats.moves.get(Direction::Left), 2);
+        assert_eq!(stats.direction_chart()[3], ("down".to_string(), 1));
+
+        stats.undo_move(Direction::Left);
+        assert_eq!(stats.total_moves, 2);
+        assert_eq!(stats.moves.get(Direction::Left), 1);
+        stats.undo_move(Direction::Up);
+        assert_eq!(stats.moves.get(Direction::Up), 0);
+
+        stats.record_board(1000, MergeRule::Classic, 128);
+        stats.record_board(400, MergeRule::Classic, 256);
+        assert_eq!(stats.best_score, 1000);
+        assert_eq!(stats.highest_tile, 256);
+        // a triples score is far larger for the same rung
+        stats.record_board(50_000, MergeRule::Triples, 512);
+        assert_eq!(stats.best_score, 1000);
+        assert_eq!(stats.highest_tile, 512);
+        assert_eq!(Stats::default().average_game_length(), 0.0);
+    }
+
+    #[test]
+    fn test_actions_raise_the_max_tile_by_rung() {
+        let tile = |value| Tile {
+            value: Value::Number(value),
+            position: Position { row: 0, col: 0 },
+        };
+        let merge = |value| {
+            Action::MergeTiles(
+                tile(value),
+                tile(value),
+                Position { row: 0, col: 1 },
+                Value::Number(value),
+            )
+        };
+        for rule in MergeRule::ALL {
+            let ladder = rule.ladder();
+            let mut current = CurrentGame::default();
+            current.record_action(&Action::SpawnRandomTile(tile(ladder[1])), rule);
+            assert_eq!(current.max_tile, 4, "{}", rule);
+            current.record_action(&merge(ladder[6]), rule);
+            current.record_action(&merge(ladder[3]), rule);
+            current.record_action(
+                &Action::SlideTile(tile(ladder[9]), Position { row: 1, col: 0 }),
+                rule,
+            );
+            assert_eq!(current.max_tile, 128, "{}", rule);
+        }
+    }
+
+    #[test]
+    fn test_edited_positions_and_puzzles_keep_no_stats() {
+        for mode in GameMode::ALL {
+            assert!(keeps_stats(mode));
+        }
+        assert!(!keeps_stats(GameMode::Sandbox));
+        assert!(!keeps_stats(GameMode::Puzzle {
+            target: 64,
+            moves: 5
+        }));
+    }
+
+    #[test]
+    fn test_bar_length() {
+        assert_eq!(bar_length(0, 10, 30.0), 0.0);
+        assert_eq!(bar_length(10, 10, 30.0), 30.0);
+        assert_eq!(bar_length(5, 10, 30.0), 15.0);
+        assert_eq!(bar_length(1, 1000, 30.0), 1.0);
+        assert_eq!(bar_length(0, 0, 30.0), 0.0);
+    }
+
+    #[test]
+    fn test_report() {
+        let mut stats = Stats::default();
+        stats.record_move(Direction::Up);
+        stats.finish_game(&mut game(1, 8, false));
+        let report = stats.report();
+        assert!(report.contains("games played  1"));
+        assert!(report.contains("    up ############################## 1"));
+        assert!(report.contains("     8 ############################## 1"));
+    }
+
+    #[test]
+    fn test_round_trip_and_old_files() {
+        let mut stats = Stats::default();
+        stats.record_move(Direction::Right);
+        stats.finish_game(&mut game(1, 16, true));
+        assert_eq!(Stats::parse(&stats.to_ron()).unwrap(), stats);
+        let old = Stats::parse("(games_played: 4)").unwrap();
+        assert_eq!(old.games_played, 4);
+        assert_eq!(old.max_tiles, BTreeMap::new());
+    }
+}
@@ -0,0 +1,413 @@
+use std::fs;
+use std::path::Path;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::accessibility::contrast_text;
+use crate::merge::MergeRule;
+use crate::tile::value::Value;
+use crate::{SquareId, SquareMarker, TextId, TextMarker};
+
+/// Folder next to the assets that is scanned for additional theme files.
+pub const THEME_DIR: &str = "assets/themes";
+
+const BUILT_IN: [&str; 4] = [
+    include_str!("../assets/themes/classic.ron"),
+    include_str!("../assets/themes/dark.ron"),
+    include_str!("../assets/themes/high_contrast.ron"),
+    include_str!("../assets/themes/colorblind.ron"),
+];
+
+/// Colors are written as `"#rrggbb"` or `"#rrggbbaa"` in theme files.
+mod hex_color {
+    use bevy::prelude::Color;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(color: &Color, serializer: S) -> Result<S::Ok, S::Error> {
+        let [r, g, b, a] = color.as_rgba_u8();
+        let hex = if a == u8::MAX {
+            format!("#{:02x}{:02x}{:02x}", r, g, b)
+        } else {
+            format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+        };
+        serializer.serialize_str(&hex)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        Color::hex(&hex).map_err(|err| D::Error::custom(format!("{}: {:?}", hex, err)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TileColors {
+    #[serde(with = "hex_color")]
+    pub background: Color,
+    #[serde(with = "hex_color")]
+    pub text: Color,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Theme {
+    pub name: String,
+    #[serde(with = "hex_color")]
+    pub background: Color,
+    #[serde(with = "hex_color")]
+    pub board: Color,
+    #[serde(with = "hex_color")]
+    pub empty_tile: Color,
+    /// Wall cells, themes from before walls get a dark gray.
+    #[serde(default = "default_wall", with = "hex_color")]
+    pub wall: Color,
+    /// Headline, score and other text outside of tiles.
+    #[serde(with = "hex_color")]
+    pub text: Color,
+    /// One entry per exponent starting at the 2 tile, larger tiles reuse
+    /// the last entry.
+    pub tiles: Vec<TileColors>,
+    /// Path of the font, relative to the assets folder.
+    pub font: String,
+    pub corner_radius: f32,
+}
+
+impl Theme {
+    pub fn parse(ron: &str) -> Result<Theme, String> {
+        let theme: Theme = ron::from_str(ron).map_err(|err| err.to_string())?;
+        if theme.tiles.is_empty() {
+            return Err(format!("theme {} has no tile colors", theme.name));
+        }
+        Ok(theme)
+    }
+
+    pub fn built_in() -> Vec<Theme> {
+        BUILT_IN
+            .iter()
+            .map(|ron| Theme::parse(ron).expect("built-in theme is valid"))
+            .collect()
+    }
+
+    fn tile(&self, value: &Value) -> Option<&TileColors> {
+        match value {
+            Value::Empty | Value::Wall => None,
+            Value::Number(_) => {
+                let index = (value.to_exponent().max(1) - 1) as usize;
+                self.tiles.get(index.min(self.tiles.len() - 1))
+            }
+        }
+    }
+
+    pub fn tile_color(&self, value: &Value) -> Color {
+        if *value == Value::Wall {
+            return self.wall;
+        }
+        self.tile(value)
+            .map(|colors| colors.background)
+            .unwrap_or(Color::NONE)
+    }
+
+    pub fn tile_text_color(&self, value: &Value) -> Color {
+        self.tile(value)
+            .map(|colors| colors.text)
+            .unwrap_or(Color::NONE)
+    }
+}
+
+fn default_wall() -> Color {
+    Color::DARK_GRAY
+}
+
+/// Reads every `*.ron` file in `dir`. Broken files are reported and skipped.
+pub fn load_theme_dir(dir: &Path) -> Vec<Theme> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return vec![];
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "ron"))
+        .collect();
+    paths.sort();
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            match fs::read_to_string(&path)
+                .map_err(|err| err.to_string())
+                .and_then(|ron| Theme::parse(&ron))
+            {
+                Ok(theme) => Some(theme),
+                Err(err) => {
+                    warn!("skipping theme {}: {}", path.display(), err);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// All known themes and the one currently in use, together with its font.
+#[derive(Resource)]
+pub struct Themes {
+    pub all: Vec<Theme>,
+    pub current: usize,
+    pub font: Handle<Font>,
+    /// Ignore the theme's tile text colors and use black or white instead.
+    pub contrast_text: bool,
+    /// The rule of the running game, tiles are colored by their rung on
+    /// its ladder.
+    pub rule: MergeRule,
+}
+
+impl Themes {
+    /// Built-in themes first; files with the same name replace them.
+    pub fn merge(mut all: Vec<Theme>, files: Vec<Theme>) -> Vec<Theme> {
+        for theme in files {
+            match all.iter_mut().find(|known| known.name == theme.name) {
+                Some(known) => *known = theme,
+                None => all.push(theme),
+            }
+        }
+        all
+    }
+
+    pub fn current(&self) -> &Theme {
+        &self.all[self.current]
+    }
+
+    pub fn tile_color(&self, value: &Value) -> Color {
+        self.current().tile_color(&self.rule.classic(value))
+    }
+
+    pub fn tile_text_color(&self, value: &Value) -> Color {
+        let theme = self.current();
+        let value = &self.rule.classic(value);
+        if self.contrast_text && *value != Value::Empty {
+            contrast_text(theme.tile_color(value))
+        } else {
+            theme.tile_text_color(value)
+        }
+    }
+
+    pub fn select(&mut self, index: usize, asset_server: &AssetServer) {
+        self.current = index % self.all.len();
+        self.font = asset_server.load(self.current().font.clone());
+    }
+
+    pub fn select_by_name(&mut self, name: &str, asset_server: &AssetServer) -> bool {
+        match self.all.iter().position(|theme| theme.name == name) {
+            Some(index) => {
+                self.select(index, asset_server);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl FromWorld for Themes {
+    fn from_world(world: &mut World) -> Self {
+        let all = Themes::merge(Theme::built_in(), load_theme_dir(Path::new(THEME_DIR)));
+        let font = world.resource::<AssetServer>().load(all[0].font.clone());
+        Themes {
+            all,
+            current: 0,
+            font,
+            contrast_text: false,
+            rule: MergeRule::Classic,
+        }
+    }
+}
+
+/// Applies theme colors, systems that adjust those colors run after it.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ThemeSet;
+
+/// Text outside of tiles that follows the theme's text color.
+#[derive(Component)]
+pub struct ThemedText;
+#[derive(Component)]
+pub struct EmptyCell;
+#[derive(Component)]
+pub struct BoardBackground;
+
+fn cycle_theme(
+    keys: Res<ButtonInput<KeyCode>>,
+    asset_server: Res<AssetServer>,
+    mut themes: ResMut<Themes>,
+) {
+    if keys.just_pressed(KeyCode::KeyT) {
+        let next = themes.current + 1;
+        themes.select(next, &asset_server);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn apply_theme(
+    themes: Res<Themes>,
+    mut clear_color: ResMut<ClearColor>,
+    tiles: Query<(&Value, &SquareId, &TextId)>,
+    mut sprites: ParamSet<(
+        Query<&mut Sprite, With<SquareMarker>>,
+        Query<&mut Sprite, With<EmptyCell>>,
+        Query<&mut Sprite, With<BoardBackground>>,
+    )>,
+    mut texts: ParamSet<(
+        Query<&mut Text, With<TextMarker>>,
+        Query<&mut Text, With<ThemedText>>,
+    )>,
+) {
+    let theme = themes.current();
+    clear_color.0 = theme.background;
+    for mut sprite in sprites.p1().iter_mut() {
+        sprite.color = theme.empty_tile;
+    }
+    for mut sprite in sprites.p2().iter_mut() {
+        sprite.color = theme.board;
+    }
+    for mut text in texts.p1().iter_mut() {
+        for section in text.sections.iter_mut() {
+            section.style.color = theme.text;
+            section.style.font = themes.font.clone();
+        }
+    }
+    for (value, square_id, text_id) in tiles.iter() {
+        if let Ok(mut sprite) = sprites.p0().get_mut(square_id.0) {
+            sprite.color = themes.tile_color(value);
+        }
+        if let Ok(mut text) = texts.p0().get_mut(text_id.0) {
+            for section in text.sections.iter_mut() {
+                section.style.color = themes.tile_text_color(value);
+                section.style.font = themes.font.clone();
+            }
+        }
+    }
+}
+
+/// Merged tiles change their value, so they have to pick up a new color.
+fn recolor_changed_tiles(
+    themes: Res<Themes>,
+    tiles: Query<(&Value, &SquareId, &TextId), Changed<Value>>,
+    mut sprites: Query<&mut Sprite, With<SquareMarker>>,
+    mut texts: Query<&mut Text, With<TextMarker>>,
+) {
+    for (value, square_id, text_id) in tiles.iter() {
+        if let Ok(mut sprite) = sprites.get_mut(square_id.0) {
+            sprite.color = themes.tile_color(value);
+        }
+        if let Ok(mut text) = texts.get_mut(text_id.0) {
+            for section in text.sections.iter_mut() {
+                section.style.color = themes.tile_text_color(value);
+            }
+        }
+    }
+}
+
+pub struct ThemePlugin;
+
+impl Plugin for ThemePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Themes>()
+            .init_resource::<ClearColor>()
+            .add_systems(PreUpdate, cycle_theme)
+            .add_systems(
+                PostUpdate,
+                (
+                    apply_theme.run_if(resource_changed::<Themes>),
+                    recolor_changed_tiles,
+                )
+                    .in_set(ThemeSet),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_built_in_themes_parse() {
+        let themes = Theme::built_in();
+        let names: Vec<_> = themes.iter().map(|theme| theme.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["classic", "dark", "high contrast", "colorblind"]
+        );
+        for theme in themes.iter() {
+            assert_eq!(theme.tiles.len(), 11, "{}", theme.name);
+        }
+    }
+
+    #[test]
+    fn test_tile_colors_by_exponent() {
+        let classic = &Theme::built_in()[0];
+        assert_eq!(
+            classic.tile_color(&Value::Number(2)),
+            Color::hex("#eee4da").unwrap()
+        );
+        assert_eq!(
+            classic.tile_text_color(&Value::Number(8)),
+            Color::hex("#f9f6f2").unwrap()
+        );
+        // larger values than the palette covers reuse the last color
+        assert_eq!(
+            classic.tile_color(&Value::Number(4096)),
+            classic.tile_color(&Value::Number(2048))
+        );
+        assert_eq!(classic.tile_color(&Value::Empty), Color::NONE);
+        assert_eq!(classic.tile_color(&Value::Wall), classic.wall);
+        assert_eq!(classic.tile_text_color(&Value::Wall), Color::NONE);
+    }
+
+    #[test]
+    fn test_tiles_are_colored_by_their_rung() {
+        let themes = Themes {
+            all: Theme::built_in(),
+            current: 0,
+            font: Handle::default(),
+            contrast_text: false,
+            rule: MergeRule::Fibonacci,
+        };
+        let classic = themes.current();
+        // 3 is the third fibonacci rung, like 8
+        assert_eq!(
+            themes.tile_color(&Value::Number(3)),
+            classic.tile_color(&Value::Number(8))
+        );
+        assert_eq!(
+            themes.tile_text_color(&Value::Number(144)),
+            classic.tile_text_color(&Value::Number(2048))
+        );
+        assert_eq!(themes.tile_color(&Value::Wall), classic.wall);
+    }
+
+    #[test]
+    fn test_theme_round_trip() {
+        let theme = Theme::built_in()[1].clone();
+        let ron = ron::to_string(&theme).unwrap();
+        assert_eq!(Theme::parse(&ron).unwrap(), theme);
+    }
+
+    #[test]
+    fn test_invalid_themes_are_rejected() {
+        assert!(Theme::parse("(name: \"x\")").is_err());
+        let bad_color = include_str!("../assets/themes/dark.ron").replace("#16161d", "#16161z");
+        assert!(Theme::parse(&bad_color).is_err());
+        let no_tiles = ron::to_string(&Theme {
+            tiles: vec![],
+            ..Theme::built_in()[0].clone()
+        })
+        .unwrap();
+        assert!(Theme::parse(&no_tiles).is_err());
+    }
+
+    #[test]
+    fn test_files_replace_built_in_themes() {
+        let mut custom = Theme::built_in()[0].clone();
+        custom.corner_radius = 20.0;
+        let mut extra = custom.clone();
+        extra.name = "extra".to_string();
+        let all = Themes::merge(Theme::built_in(), vec![custom, extra]);
+        assert_eq!(all.len(), 5);
+        assert_eq!(all[0].corner_radius, 20.0);
+        assert_eq!(all[4].name, "extra");
+    }
+}
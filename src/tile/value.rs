@@ -2,50 +2,25 @@ use bevy::prelude::*;
 use std::fmt;
 use std::str::FromStr;
 
-pub const MAX_TILE_VALUE: u32 = 2048;
-const MAX_TILE_INCREMENT: u32 = MAX_TILE_VALUE.ilog2() - 1;
-pub const EMPTY_TILE_BG_COLOR: Color = Color::rgb(0.9, 0.9, 0.9);
-
-fn lerp(a: f32, b: f32, t: f32) -> f32 {
-    a + (b - a) * t
-}
-
 #[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Value {
     Empty,
     Number(u32),
+    /// A rock that tiles can neither pass nor merge with. It never moves.
+    Wall,
 }
 
 impl Value {
-    pub fn tile_color(&self) -> Color {
-        match self {
-            Value::Empty => EMPTY_TILE_BG_COLOR.with_a(0.0),
-            Value::Number(value) => {
-                let gray: f32 = 0.7;
-                let t = (value.ilog2() - 1) as f32 / MAX_TILE_INCREMENT as f32;
-                let r = lerp(gray, 1.0, t);
-                Color::rgb(r, gray, gray)
-            }
-        }
-    }
-
     pub fn transparency_value(&self) -> f32 {
         match self {
             Value::Empty => 1.0,
-            Value::Number(_) => 0.0,
-        }
-    }
-
-    pub fn text_color(&self) -> Color {
-        match self {
-            Value::Empty => EMPTY_TILE_BG_COLOR.with_a(0.0),
-            Value::Number(value) => Color::BLACK,
+            Value::Number(_) | Value::Wall => 0.0,
         }
     }
 
     pub fn text_value(&self) -> String {
         match self {
-            Value::Empty => "".to_string(),
+            Value::Empty | Value::Wall => "".to_string(),
             Value::Number(value) => value.to_string(),
         }
     }
@@ -53,6 +28,7 @@ impl Value {
     pub fn merge(self, other: Value) -> Value {
         match self {
             Value::Empty => other,
+            Value::Wall => self,
             Value::Number(n) => {
                 if let Value::Number(m) = other {
                     Value::Number(n + m)
@@ -65,7 +41,7 @@ impl Value {
 
     pub fn to_exponent(&self) -> u32 {
         match self {
-            Value::Empty => 0,
+            Value::Empty | Value::Wall => 0,
             Value::Number(n) => n.trailing_zeros(),
         }
     }
@@ -73,6 +49,9 @@ impl Value {
 
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if *self == Value::Wall {
+            return write!(f, "X");
+        }
         match self.to_exponent() {
             0 => write!(f, "0"),
             1 => write!(f, "1"),
@@ -108,6 +87,7 @@ impl FromStr for Value {
             "9" => Ok(Value::Number(512)),
             "A" => Ok(Value::Number(1024)),
             "B" => Ok(Value::Number(2048)),
+            "X" => Ok(Value::Wall),
             _ => Err(()),
         }
     }
@@ -134,4 +114,13 @@ mod tests {
         let tile: Value = "B".parse().unwrap();
         assert_eq!(tile, Value::Number(2048));
     }
+
+    #[test]
+    fn test_walls_do_not_merge() {
+        let wall: Value = "X".parse().unwrap();
+        assert_eq!(wall, Value::Wall);
+        assert_eq!(wall.to_string(), "X");
+        assert_eq!(wall.merge(Value::Number(2)), Value::Wall);
+        assert_eq!(wall.text_value(), "");
+    }
 }